@@ -0,0 +1,113 @@
+use serde::{Serialize, Serializer};
+use std::{fmt::Display, str::FromStr};
+
+use crate::Note;
+
+/// who can see a note
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// written from the TUI or CLI, never shown to bot users
+    Local,
+    /// only the owner sees it, and only in DMs with the bot
+    Private,
+    /// shared notebook of everyone in a Discord guild
+    Guild(u64),
+    /// shared with everyone in one channel
+    Channel(u64),
+}
+
+impl Scope {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Scope::Local => "local",
+            Scope::Private => "private",
+            Scope::Guild(_) => "guild",
+            Scope::Channel(_) => "channel",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Guild(id) | Scope::Channel(id) => write!(f, "{}:{}", self.kind(), id),
+            _ => write!(f, "{}", self.kind()),
+        }
+    }
+}
+
+/// written as its `Display` form, e.g. `"guild:42"`
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, id) = match s.split_once(':') {
+            Some((kind, id)) => (kind, Some(id.parse::<u64>().map_err(|e| e.to_string())?)),
+            None => (s, None),
+        };
+        match (kind, id) {
+            ("local", None) => Ok(Scope::Local),
+            ("private", None) => Ok(Scope::Private),
+            ("guild", Some(id)) => Ok(Scope::Guild(id)),
+            ("channel", Some(id)) => Ok(Scope::Channel(id)),
+            _ => Err(format!("unknown scope {}", s)),
+        }
+    }
+}
+
+/// the Discord user asking for notes and where they asked from
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub user: String,
+    pub guild: Option<u64>,
+    pub channel: u64,
+}
+
+impl Caller {
+    fn owns(&self, note: &Note) -> bool {
+        note.owner.as_deref() == Some(self.user.as_str())
+    }
+
+    /// whether the note may be posted where the caller asked, which for the owner too means
+    /// private notes only in DMs and shared ones only in their guild or channel
+    pub fn can_see(&self, note: &Note) -> bool {
+        match note.scope {
+            Scope::Local => false,
+            Scope::Private => self.guild.is_none() && self.owns(note),
+            Scope::Guild(guild) => self.guild == Some(guild),
+            Scope::Channel(channel) => self.channel == channel,
+        }
+    }
+
+    /// shared notebooks can be edited by everyone who can see them, owners can change their
+    /// own notes from anywhere as the replies never show the text
+    pub fn can_modify(&self, note: &Note) -> bool {
+        self.can_see(note) || self.owns(note)
+    }
+
+    /// only the owner moves a note between private and shared notebooks
+    pub fn can_share(&self, note: &Note) -> bool {
+        self.owns(note)
+    }
+
+    /// resolves `private`, `guild` or `channel` to a scope for where the caller is
+    pub fn scope_named(&self, name: &str) -> Option<Scope> {
+        match name {
+            "private" => Some(Scope::Private),
+            "guild" => self.guild.map(Scope::Guild),
+            "channel" => Some(Scope::Channel(self.channel)),
+            _ => None,
+        }
+    }
+
+    /// guild notebook when asked in a guild, private in DMs
+    pub fn default_scope(&self) -> Scope {
+        self.guild.map_or(Scope::Private, Scope::Guild)
+    }
+}
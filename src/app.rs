@@ -1,14 +1,248 @@
+use std::time::{Duration, Instant};
+
 use tui::widgets::ListState;
 
-use crate::Note;
+use crate::{
+    command::{self, NoteSort},
+    fuzzy::{self, NoteMatch},
+    Attachment, Dashboard, Note, Task, TaskFilter,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToastKind {
+    Success,
+    Warning,
+    Error,
+}
+
+impl ToastKind {
+    /// how long the toast stays in the status bar
+    pub fn timeout(self) -> Duration {
+        match self {
+            ToastKind::Success => Duration::from_secs(3),
+            ToastKind::Warning => Duration::from_secs(5),
+            ToastKind::Error => Duration::from_secs(8),
+        }
+    }
+}
+
+/// a short notice in the status bar, dropped on the first tick after its timeout
+#[derive(Clone, Debug)]
+pub struct Toast {
+    pub kind: ToastKind,
+    pub text: String,
+    pub shown_at: Instant,
+}
+
+impl Toast {
+    pub fn expired(&self) -> bool {
+        self.shown_at.elapsed() >= self.kind.timeout()
+    }
+}
+
+/// an edit that lost the race against a save made elsewhere
+pub struct EditConflict {
+    pub mine: Note,
+    pub theirs: Note,
+}
+
+/// the Add prompt's popup choosing what the new note starts from
+pub struct TemplatePicker {
+    /// template names, listed after the blank note
+    pub names: Vec<String>,
+    pub state: ListState,
+}
+
+impl TemplatePicker {
+    /// selects the template picked before, if any
+    pub fn new(names: Vec<String>, current: Option<&str>) -> TemplatePicker {
+        let selected = current
+            .and_then(|current| names.iter().position(|name| name == current))
+            .map_or(0, |i| i + 1);
+        let mut state = ListState::default();
+        state.select(Some(selected));
+        TemplatePicker { names, state }
+    }
+    pub fn move_selection(&mut self, step: isize) {
+        let len = self.names.len() as isize + 1;
+        let selected = self.state.selected().unwrap_or_default() as isize;
+        self.state
+            .select(Some((selected + step).rem_euclid(len) as usize));
+    }
+    /// `None` for the blank note
+    pub fn selected(&self) -> Option<&String> {
+        let index = self.state.selected()?.checked_sub(1)?;
+        self.names.get(index)
+    }
+}
+
+/// the Ctrl-P popup jumping to a note by fuzzy title match
+pub struct QuickSwitcher {
+    pub query: String,
+    /// every note, loaded when the popup opened
+    pub notes: Vec<Note>,
+    pub matches: Vec<NoteMatch>,
+    pub state: ListState,
+    /// tab shown under the popup, restored by Esc
+    pub return_to: AppState,
+}
+
+impl QuickSwitcher {
+    pub fn new(notes: Vec<Note>, return_to: AppState, recent: &[usize]) -> QuickSwitcher {
+        let mut switcher = QuickSwitcher {
+            query: String::new(),
+            notes,
+            matches: Vec::new(),
+            state: ListState::default(),
+            return_to,
+        };
+        switcher.update(recent);
+        switcher
+    }
+    /// re-ranks the notes against the query, selecting the best match
+    pub fn update(&mut self, recent: &[usize]) {
+        self.matches = fuzzy::rank_notes(&self.query, &self.notes, recent);
+        let selected = if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.state.select(selected);
+    }
+    pub fn move_selection(&mut self, step: isize) {
+        let len = self.matches.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or_default() as isize;
+        self.state
+            .select(Some((selected + step).rem_euclid(len) as usize));
+    }
+    pub fn selected(&self) -> Option<&NoteMatch> {
+        self.matches.get(self.state.selected()?)
+    }
+}
+
+/// the `:` line at the bottom of the screen
+pub struct CommandLine {
+    pub input: String,
+    /// tab shown above the line, restored when it closes
+    pub return_to: AppState,
+    /// entry of the history shown while browsing it with Up/Down
+    history_index: Option<usize>,
+    /// candidates cycled through by Tab, empty until Tab is pressed
+    pub completions: Vec<String>,
+    completion_index: usize,
+}
+
+impl CommandLine {
+    pub fn new(return_to: AppState) -> CommandLine {
+        CommandLine {
+            input: String::new(),
+            return_to,
+            history_index: None,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+    /// typing or deleting ends history browsing and completion
+    pub fn edited(&mut self) {
+        self.history_index = None;
+        self.completions.clear();
+    }
+    /// completes the input, each further Tab moving on to the next candidate
+    pub fn complete(&mut self, themes: &[&str]) {
+        if self.completions.is_empty() {
+            self.completions = command::complete(&self.input, themes);
+            self.completion_index = 0;
+        } else {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        }
+        if let Some(candidate) = self.completions.get(self.completion_index) {
+            self.input = candidate.clone();
+        }
+    }
+    /// steps through earlier lines, most recent first
+    pub fn history_back(&mut self, history: &[String]) {
+        let index = match self.history_index {
+            _ if history.is_empty() => return,
+            Some(index) => index.saturating_sub(1),
+            None => history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = history[index].clone();
+        self.completions.clear();
+    }
+    pub fn history_forward(&mut self, history: &[String]) {
+        match self.history_index {
+            Some(index) if index + 1 < history.len() => {
+                self.history_index = Some(index + 1);
+                self.input = history[index + 1].clone();
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.input.clear();
+            }
+            None => (),
+        }
+        self.completions.clear();
+    }
+}
 
 pub struct App {
     page_state: AppState,
     pub should_exist: bool,
     note_state: ListState,
+    /// id of the selected note, so the selection survives reloads, deletes and filters
+    selected_note_id: Option<usize>,
     pub buffer: String,
     pub editor_mode: bool,
-    pub notes: Option<Vec<Note>>,
+    notes: Option<Vec<Note>>,
+    /// index into the selected note's `[[links]]`
+    pub selected_link: Option<usize>,
+    /// note ids visited before following links, most recent last
+    pub link_history: Vec<usize>,
+    pub backlinks: Vec<Note>,
+    pub attachments: Vec<Attachment>,
+    pub selected_attachment: Option<usize>,
+    /// line of the checklist item under the cursor in the detail pane
+    pub selected_task_line: Option<usize>,
+    pub tasks: Vec<Task>,
+    task_state: ListState,
+    pub task_filter: TaskFilter,
+    /// only list notes whose scope is of this kind, see [`crate::Scope::kind`]
+    pub scope_filter: Option<&'static str>,
+    /// only list notes created by this Discord user
+    pub owner_filter: Option<String>,
+    /// the last input typed into a prompt was rejected
+    pub input_error: bool,
+    /// note opened in the editor, as it was when editing started
+    pub editing: Option<Note>,
+    pub conflict: Option<EditConflict>,
+    pub switcher: Option<QuickSwitcher>,
+    /// ids of notes jumped to, most recent last
+    pub recent_notes: Vec<usize>,
+    pub command_line: Option<CommandLine>,
+    /// lines run from the command line, oldest first
+    pub command_history: Vec<String>,
+    /// outcome of the last action, shown in the status bar
+    pub toast: Option<Toast>,
+    /// notes in the database when they were last loaded, ignoring filters
+    pub note_count: usize,
+    pub sort: NoteSort,
+    /// the state the help popup was opened over
+    pub help: Option<AppState>,
+    /// template the note being added starts from
+    pub template: Option<String>,
+    pub template_picker: Option<TemplatePicker>,
+    /// template opened in the editor from the command line
+    pub editing_template: Option<String>,
+    /// daily notes, newest first
+    pub journal: Vec<Note>,
+    journal_state: ListState,
+    pub dashboard: Dashboard,
+    /// selection in the dashboard's recently edited notes
+    dashboard_state: ListState,
 }
 
 impl App {
@@ -19,11 +253,58 @@ impl App {
             page_state: AppState::Home,
             should_exist: false,
             note_state: note_state_list,
+            selected_note_id: None,
             buffer: String::new(),
             editor_mode: false,
             notes: None,
+            selected_link: None,
+            link_history: Vec::new(),
+            backlinks: Vec::new(),
+            attachments: Vec::new(),
+            selected_attachment: None,
+            selected_task_line: None,
+            tasks: Vec::new(),
+            task_state: ListState::default(),
+            task_filter: TaskFilter::Open,
+            scope_filter: None,
+            owner_filter: None,
+            input_error: false,
+            editing: None,
+            conflict: None,
+            switcher: None,
+            recent_notes: Vec::new(),
+            command_line: None,
+            command_history: Vec::new(),
+            toast: None,
+            note_count: 0,
+            sort: NoteSort::Created,
+            help: None,
+            template: None,
+            template_picker: None,
+            editing_template: None,
+            journal: Vec::new(),
+            journal_state: ListState::default(),
+            dashboard: Dashboard::default(),
+            dashboard_state: ListState::default(),
         }
     }
+    /// replaces any toast still showing
+    pub fn notify(&mut self, kind: ToastKind, text: impl Into<String>) {
+        self.toast = Some(Toast {
+            kind,
+            text: text.into(),
+            shown_at: Instant::now(),
+        });
+    }
+    pub fn success(&mut self, text: impl Into<String>) {
+        self.notify(ToastKind::Success, text)
+    }
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.notify(ToastKind::Warning, text)
+    }
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.notify(ToastKind::Error, text)
+    }
     pub fn state(&self) -> &AppState {
         &self.page_state
     }
@@ -33,11 +314,118 @@ impl App {
     pub fn set_state(&mut self, app_state: AppState) {
         self.page_state = app_state;
     }
+    /// the tab to highlight, which for a popup is the one beneath it
+    pub fn tab_state(&self) -> AppState {
+        match self.page_state {
+            AppState::Switcher => self
+                .switcher
+                .as_ref()
+                .map_or(AppState::Note, |switcher| switcher.return_to),
+            AppState::Command => self
+                .command_line
+                .as_ref()
+                .map_or(AppState::Home, |line| line.return_to),
+            AppState::Help => self.help.unwrap_or(AppState::Home),
+            AppState::Templates => AppState::Add,
+            state => state,
+        }
+    }
+    /// remembers a visit for the quick switcher's ranking
+    pub fn touch_recent(&mut self, note_id: usize) {
+        self.recent_notes.retain(|id| *id != note_id);
+        self.recent_notes.push(note_id);
+        if self.recent_notes.len() > fuzzy::RECENT_LIMIT {
+            self.recent_notes.remove(0);
+        }
+    }
     pub fn note_state(&self) -> &ListState {
         &self.note_state
     }
     pub fn set_note_state(&mut self, state: usize) {
-        self.note_state.select(Some(state))
+        self.note_state.select(Some(state));
+        self.selected_note_id = self.selected_note().and_then(|note| note.id);
+    }
+    pub fn notes(&self) -> Option<&Vec<Note>> {
+        self.notes.as_ref()
+    }
+    pub fn notes_mut(&mut self) -> Option<&mut Vec<Note>> {
+        self.notes.as_mut()
+    }
+    /// replaces the listed notes, keeping the selected note selected while it is listed
+    ///
+    /// when it is gone the selection stays at the same position, clamped to the new end
+    pub fn set_notes(&mut self, notes: Vec<Note>) {
+        if notes.is_empty() {
+            self.notes = None;
+            self.note_state.select(None);
+            return;
+        }
+        let index = notes
+            .iter()
+            .position(|note| note.id.is_some() && note.id == self.selected_note_id)
+            .unwrap_or_else(|| {
+                let previous = self.note_state.selected().unwrap_or_default();
+                previous.min(notes.len() - 1)
+            });
+        self.notes = Some(notes);
+        self.set_note_state(index);
+    }
+    /// the scope or owner filter is narrowing the notes list
+    pub fn is_filtered(&self) -> bool {
+        self.scope_filter.is_some() || self.owner_filter.is_some()
+    }
+    pub fn task_state(&self) -> &ListState {
+        &self.task_state
+    }
+    pub fn set_task_state(&mut self, state: Option<usize>) {
+        self.task_state.select(state)
+    }
+    pub fn selected_task(&self) -> Option<&Task> {
+        self.tasks.get(self.task_state.selected()?)
+    }
+    pub fn journal_state(&self) -> &ListState {
+        &self.journal_state
+    }
+    pub fn set_journal_state(&mut self, state: Option<usize>) {
+        self.journal_state.select(state)
+    }
+    pub fn selected_day(&self) -> Option<&Note> {
+        self.journal.get(self.journal_state.selected()?)
+    }
+    /// replaces the dashboard, keeping the selected recent note in range
+    pub fn set_dashboard(&mut self, dashboard: Dashboard) {
+        let selected = match self.dashboard_state.selected() {
+            _ if dashboard.recent.is_empty() => None,
+            Some(i) => Some(i.min(dashboard.recent.len() - 1)),
+            None => Some(0),
+        };
+        self.note_count = dashboard.total_notes;
+        self.dashboard = dashboard;
+        self.dashboard_state.select(selected);
+    }
+    pub fn dashboard_state(&self) -> &ListState {
+        &self.dashboard_state
+    }
+    pub fn set_dashboard_state(&mut self, state: Option<usize>) {
+        self.dashboard_state.select(state)
+    }
+    pub fn selected_recent(&self) -> Option<&Note> {
+        self.dashboard.recent.get(self.dashboard_state.selected()?)
+    }
+    /// describes the active scope/owner filters for the notes list title
+    pub fn filter_label(&self) -> String {
+        match (self.scope_filter, &self.owner_filter) {
+            (None, None) => "Notes".to_string(),
+            (Some(scope), None) => format!("Notes [{}]", scope),
+            (None, Some(owner)) => format!("Notes [owner {}]", owner),
+            (Some(scope), Some(owner)) => format!("Notes [{}, owner {}]", scope, owner),
+        }
+    }
+    pub fn selected_attachment(&self) -> Option<&Attachment> {
+        self.attachments.get(self.selected_attachment?)
+    }
+    pub fn selected_note(&self) -> Option<&Note> {
+        self.notes.as_ref()?.get(self.note_state.selected()?)
     }
 }
 
@@ -47,6 +435,47 @@ pub enum AppState {
     Note,
     Add,
     Delete,
+    Tasks,
+    /// daily notes by date
+    Journal,
+    /// due time prompt over the Notes tab
+    Schedule,
+    /// passphrase prompt shown before encrypted notes can be read
+    Unlock,
+    /// destination path prompt for the selected attachment
+    SaveAttachment,
+    /// an edit collided with a save made elsewhere
+    Conflict,
+    /// Ctrl-P quick switcher over the current tab
+    Switcher,
+    /// `:` command line under the current tab
+    Command,
+    /// the keys that work in the state beneath
+    Help,
+    /// template picker over the Add prompt
+    Templates,
+}
+
+impl AppState {
+    /// shown as the mode in the status bar
+    pub fn name(self) -> &'static str {
+        match self {
+            AppState::Home => "HOME",
+            AppState::Note => "NOTES",
+            AppState::Add => "ADD",
+            AppState::Delete => "DELETE",
+            AppState::Tasks => "TASKS",
+            AppState::Journal => "JOURNAL",
+            AppState::Schedule => "SCHEDULE",
+            AppState::Unlock => "LOCKED",
+            AppState::SaveAttachment => "SAVE",
+            AppState::Conflict => "CONFLICT",
+            AppState::Switcher => "SWITCH",
+            AppState::Command => "COMMAND",
+            AppState::Help => "HELP",
+            AppState::Templates => "TEMPLATE",
+        }
+    }
 }
 
 impl From<AppState> for usize {
@@ -56,6 +485,16 @@ impl From<AppState> for usize {
             AppState::Note => 1,
             AppState::Add => 2,
             AppState::Delete => 3,
+            AppState::Tasks => 4,
+            AppState::Journal => 5,
+            AppState::Schedule => 1,
+            AppState::Unlock => 0,
+            AppState::SaveAttachment => 1,
+            AppState::Conflict => 1,
+            AppState::Switcher => 1,
+            AppState::Command => 0,
+            AppState::Help => 0,
+            AppState::Templates => 2,
         }
     }
 }
@@ -0,0 +1,76 @@
+use std::{io::Write, path::Path, process::Command};
+
+use tempfile::TempPath;
+
+use crate::error::NoteResult;
+
+/// a file stored with a note
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub id: Option<usize>,
+    pub note_id: usize,
+    pub name: String,
+    pub size: usize,
+    /// file contents, left empty when attachments are only listed
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn new(note_id: usize, name: String, data: Vec<u8>) -> Attachment {
+        Attachment {
+            id: None,
+            note_id,
+            name,
+            size: data.len(),
+            data,
+        }
+    }
+
+    /// human readable size such as `12.3 KB`
+    pub fn display_size(&self) -> String {
+        match self.size {
+            size if size < 1024 => format!("{} B", size),
+            size if size < 1024 * 1024 => format!("{:.1} KB", size as f64 / 1024.0),
+            size => format!("{:.1} MB", size as f64 / (1024.0 * 1024.0)),
+        }
+    }
+}
+
+/// the file name of `path` without its directories, used as the attachment name
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "attachment".to_string())
+}
+
+/// the last component of a stored name, names come from imports and Discord and may
+/// carry directories or start with a dot
+fn safe_name(name: &str) -> String {
+    let name = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('.')
+        .replace(char::is_control, "");
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name
+    }
+}
+
+/// writes the decrypted attachment to a new, randomly named file only the user can read and
+/// hands it to `xdg-open`
+///
+/// the file is removed when the returned path is dropped, so keep it while the viewer runs
+pub fn open_with_system(attachment: &Attachment) -> NoteResult<TempPath> {
+    // the name goes last so the viewer is still picked by its extension
+    let mut file = tempfile::Builder::new()
+        .prefix("notes-")
+        .suffix(&format!("-{}", safe_name(&attachment.name)))
+        .tempfile()?;
+    file.write_all(&attachment.data)?;
+    let path = file.into_temp_path();
+    Command::new("xdg-open").arg(&path).spawn()?;
+    Ok(path)
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use serde::Serialize;
+
+/// requests with larger bodies are refused before being read
+const MAX_BODY: usize = 8 * 1024 * 1024;
+/// request line and headers together, so a client can't stream headers forever
+const MAX_HEAD: u64 = 16 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// header names are lowercased
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// reads one HTTP/1.1 request, `None` when the client closed the connection first
+    pub fn read(stream: &TcpStream) -> io::Result<Option<Request>> {
+        let mut reader = BufReader::new(stream).take(MAX_HEAD);
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with('\n') {
+            return Err(invalid("request line too long"));
+        }
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => return Err(invalid("malformed request line")),
+        };
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if !line.ends_with('\n') {
+                return Err(invalid("headers too large or cut short"));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let length = match headers.get("content-length") {
+            Some(length) => length
+                .parse::<usize>()
+                .map_err(|_| invalid("bad content-length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid("body too large"));
+        }
+        let mut body = vec![0; length];
+        reader.into_inner().read_exact(&mut body)?;
+        let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+        Ok(Some(Request {
+            method,
+            path: percent_decode(path),
+            query: parse_query(query),
+            headers,
+            body,
+        }))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// path split on `/`, without empty segments
+    pub fn segments(&self) -> Vec<&str> {
+        self.path
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect()
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// decodes `%XX` escapes and `+` as space, leaving malformed escapes as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &impl Serialize) -> Response {
+        let body = serde_json::to_vec_pretty(value).unwrap_or_default();
+        Response {
+            status,
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body,
+        }
+    }
+
+    pub fn error(status: u16, message: impl ToString) -> Response {
+        Response::json(status, &serde_json::json!({ "error": message.to_string() }))
+    }
+
+    pub fn empty(status: u16) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: String) -> Response {
+        self.headers.push((name, value));
+        self
+    }
+
+    pub fn write(&self, mut stream: &TcpStream) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        412 => "Precondition Failed",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// sends `raw` from a client socket and reads it back as a request on the server side
+    fn read_raw(raw: Vec<u8>) -> io::Result<Option<Request>> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            // the server may hang up before reading everything
+            let _ = stream.write_all(&raw);
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let request = Request::read(&stream);
+        drop(stream);
+        client.join().unwrap();
+        request
+    }
+
+    #[test]
+    fn reads_a_request() {
+        let raw = b"POST /notes/a%20b?q=x+y HTTP/1.1\r\nContent-Type: application/json\r\n\
+                    Content-Length: 2\r\n\r\n{}"
+            .to_vec();
+        let request = read_raw(raw).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/notes/a b");
+        assert_eq!(request.query["q"], "x y");
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[test]
+    fn refuses_oversized_headers() {
+        let mut raw = b"GET /notes HTTP/1.1\r\n".to_vec();
+        raw.extend(format!("X-Padding: {}\r\n\r\n", "a".repeat(MAX_HEAD as usize)).bytes());
+        let err = read_raw(raw).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,109 @@
+mod http;
+mod routes;
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{env, thread};
+
+use http::{Request, Response};
+use smart_notes_cli::{open_note_service, NoteService};
+
+/// listens here unless `NOTES_API_ADDR` says otherwise
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+/// how long a client may take to send its request or read the response
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// connections served at once, more are turned away instead of each taking a thread
+const MAX_CONNECTIONS: usize = 32;
+
+struct Server {
+    service: Mutex<NoteService>,
+    /// clients must send `Authorization: Bearer <token>` when set
+    token: Option<String>,
+    /// connections being served right now
+    active: AtomicUsize,
+}
+
+impl Server {
+    fn authorized(&self, request: &Request) -> bool {
+        let token = match &self.token {
+            Some(token) => token,
+            None => return true,
+        };
+        let given = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or("");
+        // compare every byte so the time taken doesn't hint at the token
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        if let Err(err) = stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        {
+            eprintln!("Error setting timeouts: {err}");
+            return;
+        }
+        let response = match Request::read(&stream) {
+            Ok(Some(request)) if !self.authorized(&request) => {
+                Response::error(401, "missing or wrong token")
+                    .with_header("WWW-Authenticate", "Bearer".to_string())
+            }
+            Ok(Some(request)) => match self.service.lock() {
+                Ok(mut service) => routes::route(&mut service, &request),
+                Err(_) => Response::error(500, "service unavailable"),
+            },
+            Ok(None) => return,
+            Err(err) => Response::error(400, err),
+        };
+        if let Err(err) = response.write(&stream) {
+            eprintln!("Error writing response: {err}");
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr: SocketAddr = env::var("NOTES_API_ADDR")
+        .unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+        .parse()?;
+    if !addr.ip().is_loopback() {
+        return Err(format!("refusing to serve notes on non-loopback address {addr}").into());
+    }
+    let db_path = env::var("NOTES_DB").unwrap_or_else(|_| "./notes.db".to_string());
+    let server = Arc::new(Server {
+        service: Mutex::new(open_note_service(db_path)?),
+        token: env::var("NOTES_API_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+        active: AtomicUsize::new(0),
+    });
+    let listener = TcpListener::bind(addr)?;
+    println!("serving notes on http://{addr}");
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if server.active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    server.active.fetch_sub(1, Ordering::SeqCst);
+                    let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+                    let _ = Response::error(503, "too many connections, try again").write(&stream);
+                    continue;
+                }
+                let server = server.clone();
+                thread::spawn(move || {
+                    server.handle(stream);
+                    server.active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            Err(err) => eprintln!("Error accepting connection: {err}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use smart_notes_cli::{Note, NoteError, NoteService};
+
+use crate::http::{Request, Response};
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+
+/// a note as the API returns it
+#[derive(Serialize)]
+struct ApiNote {
+    id: usize,
+    title: String,
+    text: String,
+    due_at: Option<DateTime<Utc>>,
+    remind_at: Option<DateTime<Utc>>,
+    completed: bool,
+    owner: Option<String>,
+    scope: String,
+    version: u64,
+    etag: String,
+}
+
+impl ApiNote {
+    fn new(note: &Note) -> ApiNote {
+        ApiNote {
+            id: note.id.unwrap_or_default(),
+            title: note.title.clone(),
+            text: note.text.clone(),
+            due_at: note.due_at,
+            remind_at: note.remind_at,
+            completed: note.completed,
+            owner: note.owner.clone(),
+            scope: note.scope.to_string(),
+            version: note.version,
+            etag: etag(note),
+        }
+    }
+}
+
+/// body of `POST /notes` and `PUT /notes/{id}`
+#[derive(Deserialize)]
+struct NoteInput {
+    title: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    remind_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    completed: bool,
+}
+
+#[derive(Serialize)]
+struct Page {
+    notes: Vec<ApiNote>,
+    total: usize,
+    offset: usize,
+    limit: usize,
+}
+
+/// the row version changes on every write, from here or from any other client
+fn etag(note: &Note) -> String {
+    format!("\"{}-{}\"", note.id.unwrap_or_default(), note.version)
+}
+
+fn status_of(err: &NoteError) -> u16 {
+    match err {
+        NoteError::NotFound(_) | NoteError::AttachmentNotFound(_) => 404,
+        NoteError::Forbidden(_) => 403,
+        NoteError::Conflict { .. } => 412,
+        NoteError::Invalid(_) => 400,
+        NoteError::Locked | NoteError::WrongPassphrase => 503,
+        _ => 500,
+    }
+}
+
+fn note_error(err: NoteError) -> Response {
+    Response::error(status_of(&err), err)
+}
+
+fn query_number(request: &Request, name: &str, default: usize) -> Result<usize, Response> {
+    match request.query.get(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Response::error(400, format!("{name} must be a number"))),
+        None => Ok(default),
+    }
+}
+
+fn page(request: &Request, notes: Vec<Note>) -> Response {
+    let offset = match query_number(request, "offset", 0) {
+        Ok(offset) => offset,
+        Err(response) => return response,
+    };
+    let limit = match query_number(request, "limit", DEFAULT_LIMIT) {
+        Ok(limit) => limit.min(MAX_LIMIT),
+        Err(response) => return response,
+    };
+    let total = notes.len();
+    let notes = notes
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(ApiNote::new)
+        .collect();
+    Response::json(
+        200,
+        &Page {
+            notes,
+            total,
+            offset,
+            limit,
+        },
+    )
+}
+
+fn with_etag(status: u16, note: &Note) -> Response {
+    Response::json(status, &ApiNote::new(note)).with_header("ETag", etag(note))
+}
+
+/// `If-Match` must name the current ETag (or `*`) when the client sends one
+fn precondition_failed(request: &Request, note: &Note) -> bool {
+    match request.header("if-match") {
+        Some(tags) => {
+            let current = etag(note);
+            !tags
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == current)
+        }
+        None => false,
+    }
+}
+
+/// writes must say they send JSON: a form or `text/plain` post is what another web page can
+/// send to localhost without the browser asking first
+fn is_json(request: &Request) -> bool {
+    request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn parse_input(request: &Request) -> Result<NoteInput, Response> {
+    if !is_json(request) {
+        return Err(Response::error(
+            415,
+            "send notes with Content-Type: application/json",
+        ));
+    }
+    let input: NoteInput = serde_json::from_slice(&request.body)
+        .map_err(|err| Response::error(400, format!("invalid note: {err}")))?;
+    if input.title.trim().is_empty() {
+        return Err(Response::error(400, "title must not be empty"));
+    }
+    Ok(input)
+}
+
+/// answers one request against the service
+pub fn route(service: &mut NoteService, request: &Request) -> Response {
+    let segments = request.segments();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["notes"]) => match service.list_all_notes() {
+            Ok(notes) => page(request, notes),
+            Err(err) => note_error(err),
+        },
+        ("GET", ["notes", "search"]) => {
+            let query = request.query.get("q").map(String::as_str).unwrap_or("");
+            match service.search_notes(query) {
+                Ok(notes) => page(request, notes),
+                Err(err) => note_error(err),
+            }
+        }
+        ("POST", ["notes"]) => create(service, request),
+        (_, ["notes"]) | (_, ["notes", "search"]) => Response::error(405, "method not allowed"),
+        (method, ["notes", id]) => match id.parse::<usize>() {
+            Ok(note_id) => note_route(service, request, method, note_id),
+            Err(_) => Response::error(404, format!("no note {id}")),
+        },
+        _ => Response::error(404, "not found"),
+    }
+}
+
+fn create(service: &mut NoteService, request: &Request) -> Response {
+    let input = match parse_input(request) {
+        Ok(input) => input,
+        Err(response) => return response,
+    };
+    let mut note = Note::new(None, input.title, input.text);
+    note.due_at = input.due_at;
+    note.remind_at = input.remind_at;
+    note.completed = input.completed;
+    let created = service
+        .create_notes(vec![note])
+        .and_then(|ids| service.fetch_note_by_id(ids[0]));
+    match created {
+        Ok(note) => with_etag(201, &note).with_header(
+            "Location",
+            format!("/notes/{}", note.id.unwrap_or_default()),
+        ),
+        Err(err) => note_error(err),
+    }
+}
+
+fn note_route(
+    service: &mut NoteService,
+    request: &Request,
+    method: &str,
+    note_id: usize,
+) -> Response {
+    let current = match service.fetch_note_by_id(note_id) {
+        Ok(note) => note,
+        Err(err) => return note_error(err),
+    };
+    match method {
+        "GET" => {
+            let fresh = request
+                .header("if-none-match")
+                .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag(&current)));
+            if fresh {
+                return Response::empty(304).with_header("ETag", etag(&current));
+            }
+            with_etag(200, &current)
+        }
+        "PUT" => {
+            if precondition_failed(request, &current) {
+                return with_etag(412, &current);
+            }
+            let input = match parse_input(request) {
+                Ok(input) => input,
+                Err(response) => return response,
+            };
+            let version = current.version;
+            let mut note = current;
+            note.title = input.title;
+            note.text = input.text;
+            note.due_at = input.due_at;
+            note.remind_at = input.remind_at;
+            note.completed = input.completed;
+            let updated = service
+                .update_note(note_id, version, note)
+                .and_then(|_| service.fetch_note_by_id(note_id));
+            match updated {
+                Ok(note) => with_etag(200, &note),
+                Err(err) => note_error(err),
+            }
+        }
+        "DELETE" => {
+            if precondition_failed(request, &current) {
+                return with_etag(412, &current);
+            }
+            match service.delete_note(note_id) {
+                Ok(_) => Response::empty(204),
+                Err(err) => note_error(err),
+            }
+        }
+        _ => Response::error(405, "method not allowed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+    use smart_notes_cli::open_note_service;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn service() -> (TempDir, NoteService) {
+        let dir = tempfile::tempdir().unwrap();
+        let service = open_note_service(dir.path().join("notes.db")).unwrap();
+        (dir, service)
+    }
+
+    fn request(method: &str, target: &str, headers: &[(&str, &str)], body: &str) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn json(response: &Response) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn create(service: &mut NoteService, title: &str) -> Response {
+        let body = format!(r#"{{"title": "{title}", "text": "body of {title}"}}"#);
+        route(
+            service,
+            &request(
+                "POST",
+                "/notes",
+                &[("content-type", "application/json")],
+                &body,
+            ),
+        )
+    }
+
+    #[test]
+    fn creates_and_reads_a_note() {
+        let (_dir, mut service) = service();
+        let created = create(&mut service, "first");
+        assert_eq!(created.status, 201);
+        assert_eq!(header(&created, "Location"), Some("/notes/1"));
+        let etag = header(&created, "ETag").unwrap().to_string();
+
+        let read = route(&mut service, &request("GET", "/notes/1", &[], ""));
+        assert_eq!(read.status, 200);
+        assert_eq!(json(&read)["title"], "first");
+
+        let cached = route(
+            &mut service,
+            &request("GET", "/notes/1", &[("if-none-match", &etag)], ""),
+        );
+        assert_eq!(cached.status, 304);
+    }
+
+    #[test]
+    fn refuses_writes_that_are_not_json() {
+        let (_dir, mut service) = service();
+        let form = route(
+            &mut service,
+            &request(
+                "POST",
+                "/notes",
+                &[("content-type", "text/plain")],
+                r#"{"title": "sneaky"}"#,
+            ),
+        );
+        assert_eq!(form.status, 415);
+        let missing = route(
+            &mut service,
+            &request("POST", "/notes", &[], r#"{"title": "sneaky"}"#),
+        );
+        assert_eq!(missing.status, 415);
+        assert_eq!(service.list_all_notes().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn put_checks_if_match() {
+        let (_dir, mut service) = service();
+        let etag = header(&create(&mut service, "first"), "ETag")
+            .unwrap()
+            .to_string();
+        let put = |etag: &str| {
+            request(
+                "PUT",
+                "/notes/1",
+                &[
+                    ("content-type", "application/json; charset=utf-8"),
+                    ("if-match", etag),
+                ],
+                r#"{"title": "renamed"}"#,
+            )
+        };
+        let updated = route(&mut service, &put(&etag));
+        assert_eq!(updated.status, 200);
+        assert_eq!(json(&updated)["title"], "renamed");
+        let stale = route(&mut service, &put(&etag));
+        assert_eq!(stale.status, 412);
+    }
+
+    #[test]
+    fn deletes_a_note() {
+        let (_dir, mut service) = service();
+        create(&mut service, "first");
+        let deleted = route(&mut service, &request("DELETE", "/notes/1", &[], ""));
+        assert_eq!(deleted.status, 204);
+        let read = route(&mut service, &request("GET", "/notes/1", &[], ""));
+        assert_eq!(read.status, 404);
+    }
+
+    #[test]
+    fn lists_and_searches_in_pages() {
+        let (_dir, mut service) = service();
+        for title in ["apple", "banana", "apricot"] {
+            create(&mut service, title);
+        }
+        let page = route(&mut service, &request("GET", "/notes?limit=2", &[], ""));
+        assert_eq!(page.status, 200);
+        assert_eq!(json(&page)["total"], 3);
+        assert_eq!(json(&page)["notes"].as_array().unwrap().len(), 2);
+
+        let found = route(&mut service, &request("GET", "/notes/search?q=ap", &[], ""));
+        assert_eq!(json(&found)["total"], 2);
+
+        let bad = route(&mut service, &request("GET", "/notes?limit=x", &[], ""));
+        assert_eq!(bad.status, 400);
+    }
+}
@@ -0,0 +1,45 @@
+use serenity::model::channel::{Message, ReactionType};
+
+/// reaction that saves a message as a note unless `DISCORD_CAPTURE_EMOJI` says otherwise
+pub const DEFAULT_CAPTURE_EMOJI: &str = "📝";
+/// name of the message context-menu command
+pub const CAPTURE_COMMAND: &str = "Save as note";
+
+const TITLE_LEN: usize = 60;
+
+/// whether the reaction is the configured capture emoji, by character or custom emoji name
+pub fn is_capture_emoji(emoji: &ReactionType, wanted: &str) -> bool {
+    match emoji {
+        ReactionType::Unicode(unicode) => unicode == wanted,
+        ReactionType::Custom { name, .. } => name.as_deref() == Some(wanted.trim_matches(':')),
+        _ => false,
+    }
+}
+
+/// title and text of the note capturing `message`, with its source kept as metadata
+pub fn note_from_message(message: &Message) -> (String, String) {
+    let first_line = message.content.lines().next().unwrap_or_default().trim();
+    let title = if first_line.is_empty() {
+        format!("Message from {}", message.author.name)
+    } else if first_line.chars().count() > TITLE_LEN {
+        let cut: String = first_line.chars().take(TITLE_LEN).collect();
+        format!("{cut}…")
+    } else {
+        first_line.to_string()
+    };
+    let mut text = message.content.clone();
+    text.push_str("\n\n---\nsource: discord\n");
+    text.push_str(&format!(
+        "author: {} ({})\n",
+        message.author.name, message.author.id
+    ));
+    text.push_str(&format!("posted: {}\n", message.timestamp));
+    text.push_str(&format!("link: {}\n", message.link()));
+    if !message.attachments.is_empty() {
+        text.push_str("attachments:\n");
+        for attachment in &message.attachments {
+            text.push_str(&format!("- {} {}\n", attachment.filename, attachment.url));
+        }
+    }
+    (title, text)
+}
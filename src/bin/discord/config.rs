@@ -0,0 +1,403 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fmt, fs};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use smart_notes_cli::Note;
+use thiserror::Error;
+
+use crate::capture::DEFAULT_CAPTURE_EMOJI;
+use crate::storage::DEFAULT_CONNECTIONS;
+
+/// read when `DISCORD_CONFIG` doesn't name another file
+const DEFAULT_CONFIG_PATH: &str = "discord.json";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("could not parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("{0} is not valid: {1}")]
+    Env(&'static str, String),
+    #[error("invalid config: {0}")]
+    Invalid(String),
+}
+
+/// how the `notes` command prints each note
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// header line followed by the whole note
+    Full,
+    /// one line per note with id, scope and title
+    Compact,
+    /// whole note inside a code block
+    Code,
+}
+
+impl OutputFormat {
+    pub fn render(&self, note: &Note) -> String {
+        let id = note.id.unwrap_or_default();
+        let scope = note.scope.kind();
+        match self {
+            OutputFormat::Full => {
+                format!("----------------- note {id} ({scope}) ------------------\n{note}")
+            }
+            OutputFormat::Compact => format!("`{id}` ({scope}) {}", note.title),
+            OutputFormat::Code => format!("note {id} ({scope})\n```\n{note}\n```"),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Full => "full",
+            OutputFormat::Compact => "compact",
+            OutputFormat::Code => "code",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(OutputFormat::Full),
+            "compact" => Ok(OutputFormat::Compact),
+            "code" => Ok(OutputFormat::Code),
+            _ => Err(format!(
+                "unknown format {s:?}, expected full, compact or code"
+            )),
+        }
+    }
+}
+
+/// bot settings from the config file, with environment variables taking precedence
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// text every command starts with
+    pub prefix: String,
+    /// guilds the bot answers in, any guild when empty
+    pub allowed_guilds: Vec<u64>,
+    /// answer in every channel that isn't denied, instead of only the allowed ones
+    pub all_channels: bool,
+    /// channels the bot answers in when `all_channels` is off
+    pub allowed_channels: Vec<u64>,
+    /// channels the bot never answers in
+    pub denied_channels: Vec<u64>,
+    /// roles allowed to run `config` commands
+    pub admin_roles: Vec<u64>,
+    pub db_path: PathBuf,
+    pub format: OutputFormat,
+    /// database connections the bot keeps open
+    pub connections: usize,
+    /// reacting with this emoji saves the message as a note
+    pub capture_emoji: String,
+    /// where reminders of local notes go, bot notes are reminded in their own notebook
+    pub reminder_channel: Option<u64>,
+    pub reminder_user: Option<u64>,
+    /// file the config was read from and `config` commands write back to
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// the settings as the file has them, before the environment overrides any
+    #[serde(skip)]
+    file: Map<String, Value>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            prefix: "!".to_string(),
+            allowed_guilds: Vec::new(),
+            all_channels: true,
+            allowed_channels: Vec::new(),
+            denied_channels: Vec::new(),
+            admin_roles: Vec::new(),
+            db_path: PathBuf::from("./notes.db"),
+            format: OutputFormat::Full,
+            connections: DEFAULT_CONNECTIONS,
+            capture_emoji: DEFAULT_CAPTURE_EMOJI.to_string(),
+            reminder_channel: None,
+            reminder_user: None,
+            path: PathBuf::from(DEFAULT_CONFIG_PATH),
+            file: Map::new(),
+        }
+    }
+}
+
+fn env_value<T: FromStr>(name: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|err: T::Err| ConfigError::Env(name, err.to_string())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// comma separated ids such as `DISCORD_ALLOWED_CHANNELS=123,456`
+fn env_ids(name: &'static str) -> Result<Option<Vec<u64>>, ConfigError> {
+    match env::var(name) {
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse()
+                    .map_err(|_| ConfigError::Env(name, format!("{id:?} is not an id")))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+impl Config {
+    /// reads `DISCORD_CONFIG` (or `discord.json` if present), applies the environment and validates
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = env::var("DISCORD_CONFIG").ok().map(PathBuf::from);
+        let mut config = match path {
+            Some(path) => Config::read(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Config::read(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Config, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.into(), err))?;
+        let file: Map<String, Value> =
+            serde_json::from_str(&content).map_err(|err| ConfigError::Parse(path.into(), err))?;
+        let mut config: Config = serde_json::from_value(Value::Object(file.clone()))
+            .map_err(|err| ConfigError::Parse(path.into(), err))?;
+        // older files only had the list, which answered everywhere while empty
+        if !file.contains_key("all_channels") {
+            config.all_channels = config.allowed_channels.is_empty();
+        }
+        config.path = path.to_path_buf();
+        config.file = file;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(prefix) = env_value("DISCORD_PREFIX")? {
+            self.prefix = prefix;
+        }
+        if let Some(guilds) = env_ids("DISCORD_ALLOWED_GUILDS")? {
+            self.allowed_guilds = guilds;
+        }
+        if let Some(channels) = env_ids("DISCORD_ALLOWED_CHANNELS")? {
+            self.all_channels = channels.is_empty();
+            self.allowed_channels = channels;
+        }
+        if let Some(roles) = env_ids("DISCORD_ADMIN_ROLES")? {
+            self.admin_roles = roles;
+        }
+        if let Some(db_path) = env_value("NOTES_DB")? {
+            self.db_path = db_path;
+        }
+        if let Some(format) = env_value("DISCORD_FORMAT")? {
+            self.format = format;
+        }
+        if let Some(connections) = env_value("DISCORD_DB_CONNECTIONS")? {
+            self.connections = connections;
+        }
+        if let Some(emoji) = env_value("DISCORD_CAPTURE_EMOJI")? {
+            self.capture_emoji = emoji;
+        }
+        if let Some(channel) = env_value("DISCORD_REMINDER_CHANNEL")? {
+            self.reminder_channel = Some(channel);
+        }
+        if let Some(user) = env_value("DISCORD_REMINDER_USER")? {
+            self.reminder_user = Some(user);
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.prefix.is_empty() || self.prefix.chars().any(char::is_whitespace) {
+            return Err(ConfigError::Invalid(
+                "prefix must be non-empty without spaces".into(),
+            ));
+        }
+        if self.connections == 0 {
+            return Err(ConfigError::Invalid(
+                "connections must be at least 1".into(),
+            ));
+        }
+        if self.capture_emoji.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "capture_emoji must not be empty".into(),
+            ));
+        }
+        if self.reminder_channel.is_some() && self.reminder_user.is_some() {
+            return Err(ConfigError::Invalid(
+                "set reminder_channel or reminder_user, not both".into(),
+            ));
+        }
+        let db_dir = self
+            .db_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty());
+        if let Some(dir) = db_dir.filter(|dir| !dir.is_dir()) {
+            return Err(ConfigError::Invalid(format!(
+                "database directory {} does not exist",
+                dir.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// whether commands are answered in this channel
+    pub fn allows(&self, guild: Option<u64>, channel: u64) -> bool {
+        let guild_allowed = self.allowed_guilds.is_empty()
+            || guild.is_some_and(|guild| self.allowed_guilds.contains(&guild));
+        let channel_allowed = !self.denied_channels.contains(&channel)
+            && (self.all_channels || self.allowed_channels.contains(&channel));
+        guild_allowed && channel_allowed
+    }
+
+    pub fn allow_channel(&mut self, channel: u64) {
+        self.denied_channels.retain(|id| *id != channel);
+        if !self.all_channels && !self.allowed_channels.contains(&channel) {
+            self.allowed_channels.push(channel);
+        }
+    }
+
+    /// stops answering in the channel, denying the last allowed one leaves the bot silent
+    pub fn deny_channel(&mut self, channel: u64) {
+        self.allowed_channels.retain(|id| *id != channel);
+        if !self.denied_channels.contains(&channel) {
+            self.denied_channels.push(channel);
+        }
+    }
+
+    /// answers in every channel again
+    pub fn reset_channels(&mut self) {
+        self.all_channels = true;
+        self.allowed_channels.clear();
+        self.denied_channels.clear();
+    }
+
+    pub fn is_admin(&self, roles: &[u64]) -> bool {
+        roles.iter().any(|role| self.admin_roles.contains(role))
+    }
+
+    /// writes the channel settings back to the file it came from, everything else is kept as
+    /// the file has it so values from the environment don't end up saved
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let mut file = self.file.clone();
+        file.insert("all_channels".into(), self.all_channels.into());
+        file.insert(
+            "allowed_channels".into(),
+            self.allowed_channels.clone().into(),
+        );
+        file.insert(
+            "denied_channels".into(),
+            self.denied_channels.clone().into(),
+        );
+        let content = serde_json::to_string_pretty(&file)
+            .map_err(|err| ConfigError::Parse(self.path.clone(), err))?;
+        fs::write(&self.path, content).map_err(|err| ConfigError::Io(self.path.clone(), err))
+    }
+
+    /// summary printed by `config show`
+    pub fn describe(&self) -> String {
+        let ids = |ids: &[u64], empty: &str| {
+            if ids.is_empty() {
+                empty.to_string()
+            } else {
+                ids.iter()
+                    .map(u64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        };
+        let channels = match (self.all_channels, self.denied_channels.is_empty()) {
+            (true, true) => "any".to_string(),
+            (true, false) => format!("any except {}", ids(&self.denied_channels, "")),
+            (false, _) => ids(&self.allowed_channels, "none"),
+        };
+        format!(
+            "prefix: `{}`\nguilds: {}\nchannels: {}\nadmin roles: {}\nformat: {}",
+            self.prefix,
+            ids(&self.allowed_guilds, "any"),
+            channels,
+            ids(&self.admin_roles, "none"),
+            self.format,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(json: &str) -> (tempfile::TempDir, Config) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("discord.json");
+        fs::write(&path, json).unwrap();
+        let config = Config::read(&path).unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn denying_the_last_allowed_channel_closes_the_bot() {
+        let (_dir, mut config) = read(r#"{"allowed_channels": [1]}"#);
+        assert!(!config.all_channels);
+        assert!(config.allows(None, 1) && !config.allows(None, 2));
+        config.deny_channel(1);
+        assert!(!config.allows(None, 1) && !config.allows(None, 2));
+        config.allow_channel(2);
+        assert!(!config.allows(None, 1) && config.allows(None, 2));
+    }
+
+    #[test]
+    fn deny_blocks_a_channel_while_all_are_allowed() {
+        let (_dir, mut config) = read("{}");
+        assert!(config.all_channels && config.allows(None, 1));
+        config.deny_channel(1);
+        assert!(!config.allows(None, 1) && config.allows(None, 2));
+        assert_eq!(
+            config.describe().lines().nth(2),
+            Some("channels: any except 1")
+        );
+        config.allow_channel(1);
+        assert!(config.allows(None, 1));
+        assert!(config.allowed_channels.is_empty());
+    }
+
+    #[test]
+    fn save_keeps_the_file_settings_instead_of_overrides() {
+        let (_dir, mut config) = read(r#"{"prefix": "?", "unknown": 1}"#);
+        // as `apply_env` would set them from NOTES_DB and DISCORD_ADMIN_ROLES
+        config.db_path = PathBuf::from("/elsewhere/notes.db");
+        config.admin_roles = vec![7];
+        config.deny_channel(3);
+        config.save().unwrap();
+
+        let saved: Map<String, Value> =
+            serde_json::from_str(&fs::read_to_string(&config.path).unwrap()).unwrap();
+        assert_eq!(saved["prefix"], "?");
+        assert_eq!(saved["unknown"], 1);
+        assert_eq!(saved["denied_channels"], serde_json::json!([3]));
+        assert!(!saved.contains_key("db_path") && !saved.contains_key("admin_roles"));
+
+        let reread = Config::read(&config.path).unwrap();
+        assert!(reread.all_channels && !reread.allows(None, 3));
+    }
+}
@@ -1,54 +1,513 @@
-use std::{env, sync::Arc};
+mod capture;
+mod config;
+mod storage;
 
+use std::env;
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use config::{Config, OutputFormat};
+use serenity::all::{
+    Command, CommandInteraction, CommandType, CreateCommand, CreateInteractionResponse,
+    CreateInteractionResponseMessage, Interaction,
+};
 use serenity::async_trait;
-use serenity::model::channel::Message;
+use serenity::http::Http;
+use serenity::model::channel::{Message, Reaction};
+use serenity::model::gateway::Ready;
+use serenity::model::id::{ChannelId, GuildId, UserId};
 use serenity::prelude::*;
-use smart_notes_cli::{get_note_service, Note, NoteService};
-use tokio;
+use smart_notes_cli::{
+    format_when, open_note_service, parse_duration, parse_when, Caller, Note, Scope,
+};
+use storage::Storage;
+
+/// how often the scheduler looks for reminders that are due
+const REMINDER_POLL: Duration = Duration::from_secs(30);
+
+/// where reminders of local notes get posted
+#[derive(Clone, Copy)]
+enum ReminderTarget {
+    Channel(ChannelId),
+    User(UserId),
+}
+
+impl ReminderTarget {
+    fn from_config(config: &Config) -> Option<ReminderTarget> {
+        if let Some(channel) = config.reminder_channel {
+            return Some(ReminderTarget::Channel(ChannelId::new(channel)));
+        }
+        config
+            .reminder_user
+            .map(|user| ReminderTarget::User(UserId::new(user)))
+    }
+
+    async fn channel(&self, http: &Http) -> serenity::Result<ChannelId> {
+        match self {
+            ReminderTarget::Channel(channel) => Ok(*channel),
+            ReminderTarget::User(user) => Ok(user.create_dm_channel(http).await?.id),
+        }
+    }
+}
+
+/// where a fired reminder may be posted without showing the note to anyone who could not
+/// list it: the owner's DMs for private notes, the note's channel or guild for shared ones
+/// and the configured target for local notes, `None` when there is nowhere
+///
+/// guild notes go to the guild's system channel only if the bot may talk there
+async fn reminder_channel(
+    http: &Http,
+    note: &Note,
+    local: Option<ReminderTarget>,
+    config: &Config,
+) -> serenity::Result<Option<ChannelId>> {
+    let owner = note
+        .owner
+        .as_deref()
+        .and_then(|owner| owner.parse::<NonZeroU64>().ok())
+        .map(UserId::from);
+    let owner_dm = || async move {
+        match owner {
+            Some(owner) => Ok(Some(owner.create_dm_channel(http).await?.id)),
+            None => Ok(None),
+        }
+    };
+    match note.scope {
+        Scope::Local => match local {
+            Some(target) => Ok(Some(target.channel(http).await?)),
+            None => Ok(None),
+        },
+        Scope::Private => owner_dm().await,
+        Scope::Channel(channel) => Ok(NonZeroU64::new(channel).map(ChannelId::from)),
+        Scope::Guild(guild) => {
+            let system_channel = match NonZeroU64::new(guild) {
+                Some(guild) => {
+                    GuildId::from(guild)
+                        .to_partial_guild(http)
+                        .await?
+                        .system_channel_id
+                }
+                None => None,
+            };
+            match system_channel.filter(|channel| config.allows(Some(guild), channel.get())) {
+                Some(channel) => Ok(Some(channel)),
+                None => owner_dm().await,
+            }
+        }
+    }
+}
+
+/// schedule change requested by `due`, `remind`, `snooze` or `done`
+enum Schedule {
+    Due(chrono::DateTime<chrono::Utc>),
+    Remind(chrono::DateTime<chrono::Utc>),
+    Snooze(chrono::Duration),
+    Done,
+}
 
 struct Handler {
-    service: Arc<Mutex<NoteService>>,
+    storage: Storage,
+    reminder_target: Option<ReminderTarget>,
+    scheduler_started: AtomicBool,
+    /// settings `config` commands can change while the bot runs, shared with the scheduler
+    config: Arc<RwLock<Config>>,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
-        if msg.content == "!ping" {
-            if let Err(why) = msg.channel_id.say(&ctx.http, "Pong!").await {
-                println!("Error sending message: {why:?}");
+        let config = self.config.read().await.clone();
+        let content = match msg.content.strip_prefix(config.prefix.as_str()) {
+            Some(content) => content,
+            None => return,
+        };
+        let caller = Caller {
+            user: msg.author.id.get().to_string(),
+            guild: msg.guild_id.map(|guild| guild.get()),
+            channel: msg.channel_id.get(),
+        };
+        let mut words = content.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        // admins can bind the bot from any channel, even one it doesn't answer in yet
+        if command != "config" && !config.allows(caller.guild, caller.channel) {
+            return;
+        }
+        let reply = match command {
+            "ping" => "Pong!".to_string(),
+            "notes" => {
+                self.notes_command(&ctx, &msg, caller, config.format).await;
+                return;
+            }
+            "due" | "remind" | "snooze" | "done" => {
+                self.schedule_command(&caller, &config.prefix, command, &args)
+                    .await
             }
+            "add" => self.add_command(&caller, &config.prefix, &args).await,
+            "share" => self.share_command(&caller, &config.prefix, &args).await,
+            "config" => self.config_command(&msg, &args).await,
+            _ => return,
+        };
+        if let Err(why) = msg.channel_id.say(&ctx.http, reply).await {
+            println!("Error sending message: {why:?}")
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let config = self.config.read().await;
+        let guild = reaction.guild_id.map(|guild| guild.get());
+        if !capture::is_capture_emoji(&reaction.emoji, &config.capture_emoji)
+            || !config.allows(guild, reaction.channel_id.get())
+        {
+            return;
+        }
+        drop(config);
+        let user = match reaction.user_id {
+            Some(user) => user,
+            None => return,
+        };
+        let message = match reaction.message(&ctx.http).await {
+            Ok(message) => message,
+            Err(why) => {
+                println!("Error fetching reacted message: {why:?}");
+                return;
+            }
+        };
+        let caller = Caller {
+            user: user.get().to_string(),
+            guild,
+            channel: reaction.channel_id.get(),
+        };
+        let reply = self.capture_message(&caller, &message).await;
+        if let Err(why) = message.reply(&ctx.http, reply).await {
+            println!("Error sending message: {why:?}")
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let command = match interaction {
+            Interaction::Command(command) if command.data.name == capture::CAPTURE_COMMAND => {
+                command
+            }
+            _ => return,
+        };
+        let allowed = self.config.read().await.allows(
+            command.guild_id.map(|guild| guild.get()),
+            command.channel_id.get(),
+        );
+        let reply = if allowed {
+            self.capture_command(&command).await
+        } else {
+            "notes are not enabled in this channel".to_string()
+        };
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(reply)
+                .ephemeral(true),
+        );
+        if let Err(why) = command.create_response(&ctx.http, response).await {
+            println!("Error responding to command: {why:?}")
+        }
+    }
+
+    async fn ready(&self, ctx: Context, _ready: Ready) {
+        let capture = CreateCommand::new(capture::CAPTURE_COMMAND).kind(CommandType::Message);
+        if let Err(why) = Command::create_global_command(&ctx.http, capture).await {
+            println!("Error registering context menu: {why:?}");
         }
-        if msg.content == "!notes" {
-            let notes = self.read_notes().await;
-            for (i, note) in notes.iter().enumerate() {
-                let note_str = format!("----------------- note {i} ------------------\n{note}");
-                if let Err(why) = msg.channel_id.say(&ctx.http, note_str).await {
+        let target = self.reminder_target;
+        let config = self.config.clone();
+        // ready fires again on reconnect, only one scheduler should run
+        if self.scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let storage = self.storage.clone();
+        let http = ctx.http.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REMINDER_POLL);
+            loop {
+                interval.tick().await;
+                let config = config.read().await.clone();
+                deliver_reminders(&storage, &http, target, &config).await;
+            }
+        });
+    }
+}
+
+impl Handler {
+    /// handles `notes`, posting every note the caller can see
+    async fn notes_command(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        caller: Caller,
+        format: OutputFormat,
+    ) {
+        let notes = match self
+            .storage
+            .run(move |service| service.list_visible_notes(&caller))
+            .await
+        {
+            Ok(notes) => notes,
+            Err(why) => {
+                if let Err(why) = msg
+                    .channel_id
+                    .say(&ctx.http, format!("could not list notes: {why}"))
+                    .await
+                {
                     println!("Error sending message: {why:?}")
                 }
+                return;
+            }
+        };
+        for note in notes.iter() {
+            if let Err(why) = msg.channel_id.say(&ctx.http, format.render(note)).await {
+                println!("Error sending message: {why:?}")
             }
         }
     }
+
+    /// handles `config show`, `config allow|deny [here|<channel>]` and `config reset` for admins
+    async fn config_command(&self, msg: &Message, args: &[&str]) -> String {
+        let roles: Vec<u64> = msg
+            .member
+            .as_ref()
+            .map(|member| member.roles.iter().map(|role| role.get()).collect())
+            .unwrap_or_default();
+        let mut config = self.config.write().await;
+        if !config.is_admin(&roles) {
+            return "only admins can change the bot config".to_string();
+        }
+        let prefix = config.prefix.clone();
+        let usage = format!(
+            "usage: {prefix}config show|reset|allow [here|<channel>]|deny [here|<channel>]"
+        );
+        let channel = match args.get(1).copied().unwrap_or("here") {
+            "here" => Some(msg.channel_id.get()),
+            mention => mention
+                .trim_start_matches("<#")
+                .trim_end_matches('>')
+                .parse::<u64>()
+                .ok(),
+        };
+        match (args.first().copied(), channel) {
+            (Some("show"), _) => return config.describe(),
+            (Some("allow"), Some(channel)) => config.allow_channel(channel),
+            (Some("deny"), Some(channel)) => config.deny_channel(channel),
+            (Some("reset"), _) => config.reset_channels(),
+            _ => return usage,
+        }
+        match config.save() {
+            Ok(_) => config.describe(),
+            Err(why) => format!("{}\nnot saved: {why}", config.describe()),
+        }
+    }
+
+    /// stores the message as a note owned by the caller and says which id it got
+    async fn capture_message(&self, caller: &Caller, message: &Message) -> String {
+        let (title, text) = capture::note_from_message(message);
+        let caller = caller.clone();
+        let created = self
+            .storage
+            .write(move |service| {
+                let scope = caller.default_scope();
+                service.create_owned_note(&caller, scope, title, text)
+            })
+            .await;
+        match created {
+            Ok(note_id) => format!("saved as note {note_id}"),
+            Err(why) => format!("could not save note: {why}"),
+        }
+    }
+
+    async fn capture_command(&self, command: &CommandInteraction) -> String {
+        let message = match command
+            .data
+            .target_id
+            .and_then(|target| command.data.resolved.messages.get(&target.to_message_id()))
+        {
+            Some(message) => message,
+            None => return "could not find that message".to_string(),
+        };
+        let caller = Caller {
+            user: command.user.id.get().to_string(),
+            guild: command.guild_id.map(|guild| guild.get()),
+            channel: command.channel_id.get(),
+        };
+        self.capture_message(&caller, message).await
+    }
+
+    /// handles `add [private|guild|channel] <title> | <text>`
+    async fn add_command(&self, caller: &Caller, prefix: &str, args: &[&str]) -> String {
+        let usage = format!("usage: {prefix}add [private|guild|channel] <title> | <text>");
+        let (scope, rest) = match args.first().and_then(|name| caller.scope_named(name)) {
+            Some(scope) => (scope, &args[1..]),
+            None => (caller.default_scope(), args),
+        };
+        let rest = rest.join(" ");
+        let (title, text) = rest.split_once('|').unwrap_or((rest.as_str(), ""));
+        if title.trim().is_empty() {
+            return usage;
+        }
+        let (caller, title, text) = (
+            caller.clone(),
+            title.trim().to_string(),
+            text.trim().to_string(),
+        );
+        let created = self
+            .storage
+            .write(move |service| service.create_owned_note(&caller, scope, title, text))
+            .await;
+        match created {
+            Ok(note_id) => format!("created note {note_id} ({})", scope.kind()),
+            Err(why) => format!("could not create note: {why}"),
+        }
+    }
+
+    /// handles `share <id> <private|guild|channel>`
+    async fn share_command(&self, caller: &Caller, prefix: &str, args: &[&str]) -> String {
+        let usage = format!("usage: {prefix}share <id> <private|guild|channel>");
+        let (note_id, scope) = match args {
+            [id, name] => match (id.parse::<usize>(), caller.scope_named(name)) {
+                (Ok(note_id), Some(scope)) => (note_id, scope),
+                _ => return usage,
+            },
+            _ => return usage,
+        };
+        let caller = caller.clone();
+        let shared = self
+            .storage
+            .write(move |service| service.share_note(&caller, note_id, scope))
+            .await;
+        match shared {
+            Ok(_) => format!("note {note_id} is now {}", scope.kind()),
+            Err(why) => format!("could not share note {note_id}: {why}"),
+        }
+    }
+
+    /// handles `due <id> <when>`, `remind <id> <when>`, `snooze <id> <span>` and `done <id>`
+    async fn schedule_command(
+        &self,
+        caller: &Caller,
+        prefix: &str,
+        command: &str,
+        args: &[&str],
+    ) -> String {
+        let usage = format!(
+            "usage: {prefix}due|{prefix}remind <id> <+30m|YYYY-MM-DD [HH:MM]>, {prefix}snooze <id> <30m>, {prefix}done <id>"
+        );
+        let note_id = match args.first().and_then(|id| id.parse::<usize>().ok()) {
+            Some(note_id) => note_id,
+            None => return usage,
+        };
+        let rest = args[1..].join(" ");
+        let change = match command {
+            "due" | "remind" => match parse_when(&rest, chrono::Utc::now()) {
+                Some(when) if command == "due" => Schedule::Due(when),
+                Some(when) => Schedule::Remind(when),
+                None => return usage,
+            },
+            "snooze" => match parse_duration(&rest) {
+                Some(delay) => Schedule::Snooze(delay),
+                None => return usage,
+            },
+            _ => Schedule::Done,
+        };
+        let caller = caller.clone();
+        let result = self
+            .storage
+            .write(move |service| {
+                service.fetch_modifiable(&caller, note_id)?;
+                match change {
+                    Schedule::Due(when) => service.set_due(note_id, Some(when)),
+                    Schedule::Remind(when) => service.set_reminder(note_id, Some(when)),
+                    Schedule::Snooze(delay) => service.snooze(note_id, delay),
+                    Schedule::Done => service.complete(note_id),
+                }
+            })
+            .await;
+        match result {
+            Ok(note) if note.completed => format!("note {note_id} marked done"),
+            Ok(note) => match note.remind_at {
+                Some(at) => format!("note {note_id} will remind at {}", format_when(at)),
+                None => format!("note {note_id} updated"),
+            },
+            Err(why) => format!("could not update note {note_id}: {why}"),
+        }
+    }
 }
-impl Handler {
-    async fn read_notes(&self) -> Vec<Note> {
-        let notes = self.service.lock().await.list_all_notes().unwrap();
-        notes
+
+/// posts every due reminder, only clearing the ones that were actually delivered
+async fn deliver_reminders(
+    storage: &Storage,
+    http: &Http,
+    target: Option<ReminderTarget>,
+    config: &Config,
+) {
+    let now = chrono::Utc::now();
+    let due = match storage.run(move |service| service.due_reminders(now)).await {
+        Ok(due) => due,
+        Err(why) => {
+            println!("Error loading reminders: {why:?}");
+            return;
+        }
+    };
+    for note in due {
+        let note_id = match note.id {
+            Some(note_id) => note_id,
+            None => continue,
+        };
+        let channel = match reminder_channel(http, &note, target, config).await {
+            Ok(Some(channel)) => channel,
+            // kept pending until a target is configured
+            Ok(None) => continue,
+            Err(why) => {
+                println!("Error finding where to send reminder {note_id}: {why:?}");
+                continue;
+            }
+        };
+        let prefix = &config.prefix;
+        let content = format!(
+            "⏰ reminder for note {note_id}\n{note}\n`{prefix}snooze {note_id} 1h` or `{prefix}done {note_id}`"
+        );
+        if let Err(why) = channel.say(http, content).await {
+            println!("Error sending reminder: {why:?}");
+            continue;
+        }
+        if let Err(why) = storage
+            .write(move |service| service.mark_reminded(note_id))
+            .await
+        {
+            println!("Error clearing reminder: {why:?}");
+        }
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // refuse to start on a bad config rather than misbehave later
+    let config = Config::load()?;
     // Login with a bot token from the environment
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+        | GatewayIntents::MESSAGE_CONTENT
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::DIRECT_MESSAGE_REACTIONS;
 
     // Create a new instance of the Client, logging in as a bot.
-    let service = get_note_service()?;
-    let service = Arc::new(Mutex::new(service));
-    let handler = Handler { service };
+    let storage = Storage::open(config.connections, storage::DEFAULT_TIMEOUT, || {
+        open_note_service(&config.db_path)
+    })?;
+    let handler = Handler {
+        storage,
+        reminder_target: ReminderTarget::from_config(&config),
+        scheduler_started: AtomicBool::new(false),
+        config: Arc::new(RwLock::new(config)),
+    };
     let mut client = Client::builder(&token, intents)
         .event_handler(handler)
         .await
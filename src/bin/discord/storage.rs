@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use smart_notes_cli::{NoteError, NoteService, ResultDynError};
+use thiserror::Error;
+
+/// connections opened unless `DISCORD_DB_CONNECTIONS` says otherwise
+pub const DEFAULT_CONNECTIONS: usize = 4;
+/// how long a read may wait for the database before the user gets an error
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error(transparent)]
+    Note(#[from] NoteError),
+    #[error("the database took too long to answer, try again")]
+    Timeout,
+    #[error("storage task failed: {0}")]
+    Failed(String),
+}
+
+/// pool of note services, each on its own SQLite connection, used from the blocking thread pool
+///
+/// the database runs in WAL mode so reads on one connection go on while another writes
+#[derive(Clone)]
+pub struct Storage {
+    connections: Arc<Vec<Mutex<NoteService>>>,
+    next: Arc<AtomicUsize>,
+    timeout: Duration,
+}
+
+impl Storage {
+    /// opens `size` connections with `open`, which also unlocks encrypted databases
+    pub fn open(
+        size: usize,
+        timeout: Duration,
+        open: impl Fn() -> ResultDynError<NoteService>,
+    ) -> ResultDynError<Storage> {
+        let connections = (0..size.max(1))
+            .map(|_| open().map(Mutex::new))
+            .collect::<ResultDynError<Vec<_>>>()?;
+        Ok(Storage {
+            connections: Arc::new(connections),
+            next: Arc::new(AtomicUsize::new(0)),
+            timeout,
+        })
+    }
+
+    /// runs a read on a free connection without blocking the async runtime, giving up after
+    /// the timeout
+    pub async fn run<T, F>(&self, task: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut NoteService) -> Result<T, NoteError> + Send + 'static,
+    {
+        match tokio::time::timeout(self.timeout, self.spawn(task)).await {
+            Ok(result) => result,
+            Err(_) => Err(StorageError::Timeout),
+        }
+    }
+
+    /// like [`Storage::run`] but waits as long as the write takes
+    ///
+    /// a blocking task can't be cancelled, so after a timeout the write would still be saved
+    /// while the user is told to try again and saves it twice
+    pub async fn write<T, F>(&self, task: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut NoteService) -> Result<T, NoteError> + Send + 'static,
+    {
+        self.spawn(task).await
+    }
+
+    async fn spawn<T, F>(&self, task: F) -> Result<T, StorageError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut NoteService) -> Result<T, NoteError> + Send + 'static,
+    {
+        let connections = self.connections.clone();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let handle = tokio::task::spawn_blocking(move || {
+            let count = connections.len();
+            // take the first idle connection, otherwise queue on the one round robin picked
+            let mut service = (0..count)
+                .find_map(|offset| connections[(start + offset) % count].try_lock().ok())
+                .unwrap_or_else(|| match connections[start % count].lock() {
+                    Ok(service) => service,
+                    Err(poisoned) => poisoned.into_inner(),
+                });
+            task(&mut service)
+        });
+        match handle.await {
+            Ok(result) => Ok(result?),
+            Err(why) => Err(StorageError::Failed(why.to_string())),
+        }
+    }
+}
@@ -1,16 +1,23 @@
+mod rpc;
+
 use std::{
-    io,
+    env, fs, io,
+    path::PathBuf,
     sync::mpsc::{self, Receiver, Sender},
     thread,
     time::{self, Duration, Instant},
 };
 
 use crossterm::{
-    event::{self, Event as CEvent},
+    event::{self, Event as CEvent, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use smart_notes_cli::{get_handler, CrossTerminal, Event};
+use smart_notes_cli::{
+    edit_text_with_vim_at, expand_template, get_handler, get_note_service, parse_when,
+    passphrase_from_env, sync_dir, Config, CrossTerminal, Event, ExportedNote, Note, NoteService,
+    Prefer, SyncReport,
+};
 use tui::{backend::CrosstermBackend, Terminal};
 
 fn initialized_terminal() -> Result<CrossTerminal, Box<dyn std::error::Error>> {
@@ -30,7 +37,283 @@ fn cleanup_terminal(mut terminal: CrossTerminal) -> Result<(), Box<dyn std::erro
     Ok(())
 }
 
+/// how often `sync --watch` looks for changes on either side
+const SYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+const USAGE: &str =
+    "usage: notes [due <id> <when> | remind <id> <when> | done <id> | encrypt | rekey | rpc
+             | sync <dir> [--watch] [--prefer db|disk]
+             | attach <id> <file> | attachments <id> | extract <attachment-id> <file>
+             | detach <attachment-id> | export <file> | import <file>
+             | template list | template show|remove <name> | template add <name> [<file>]
+             | template use <name> <title> | today]
+  <when> is +30m, +2h, +1d, YYYY-MM-DD or \"YYYY-MM-DD HH:MM\"
+  templates may use {{date}}, {{time}}, {{title}} and {{cursor}}
+  today opens the daily note in vim, starting it from the journal_template if it is new
+  export writes every note with its attachments as JSON, import adds them as new notes";
+
+/// reads a line from the terminal without echoing it
+fn prompt_passphrase(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    eprint!("{}: ", prompt);
+    enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(CEvent::Key(key)) => {
+                // raw mode turns Ctrl-C into a key press instead of a signal
+                let control = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Enter => break Ok(()),
+                    KeyCode::Esc => break Err("cancelled".into()),
+                    KeyCode::Char('c') if control => break Err("cancelled".into()),
+                    KeyCode::Backspace => {
+                        passphrase.pop();
+                    }
+                    KeyCode::Char(c) if !control => passphrase.push(c),
+                    _ => (),
+                }
+            }
+            Ok(_) => (),
+            Err(err) => break Err(err.into()),
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+    result.map(|_| passphrase)
+}
+
+fn prompt_new_passphrase() -> Result<String, Box<dyn std::error::Error>> {
+    let passphrase = prompt_passphrase("new passphrase")?;
+    if passphrase.is_empty() {
+        return Err("passphrase must not be empty".into());
+    }
+    if prompt_passphrase("repeat passphrase")? != passphrase {
+        return Err("passphrases do not match".into());
+    }
+    Ok(passphrase)
+}
+
+/// `encrypt` and `rekey` work on the raw database instead of an unlocked service
+fn run_key_command(command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut service = NoteService::new("./notes.db")?;
+    service.initialize_notes_service()?;
+    match command {
+        "encrypt" => {
+            if service.is_encrypted()? {
+                return Err("notes are already encrypted, use `notes rekey`".into());
+            }
+            let passphrase = match passphrase_from_env()? {
+                Some(passphrase) => passphrase,
+                None => prompt_new_passphrase()?,
+            };
+            service.enable_encryption(&passphrase)?;
+            println!("notes encrypted");
+        }
+        _ => {
+            let old = match passphrase_from_env()? {
+                Some(passphrase) => passphrase,
+                None => prompt_passphrase("current passphrase")?,
+            };
+            let new = prompt_new_passphrase()?;
+            service.change_passphrase(&old, &new)?;
+            println!("passphrase changed");
+        }
+    }
+    Ok(())
+}
+
+fn print_report(report: &SyncReport) {
+    let ids = |ids: &[usize]| {
+        ids.iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let lines = [
+        ("wrote", &report.written),
+        ("applied", &report.applied),
+        ("created", &report.created),
+        ("removed files of", &report.removed_files),
+        ("deleted", &report.removed_notes),
+        ("renamed", &report.renamed),
+    ];
+    for (action, changed) in lines {
+        if !changed.is_empty() {
+            println!("{} note {}", action, ids(changed));
+        }
+    }
+    for conflict in &report.conflicts {
+        match conflict.note_id {
+            Some(note_id) => eprintln!(
+                "conflict on note {} ({}): {}",
+                note_id,
+                conflict.path.display(),
+                conflict.reason
+            ),
+            None => eprintln!(
+                "conflict on {}: {}",
+                conflict.path.display(),
+                conflict.reason
+            ),
+        }
+    }
+}
+
+/// `sync DIR [--watch] [--prefer db|disk]`
+fn run_sync(service: &mut NoteService, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut dir = None;
+    let mut watch = false;
+    let mut prefer = Prefer::Neither;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            "--prefer" => {
+                prefer = match args.next().map(String::as_str) {
+                    Some("db") => Prefer::Database,
+                    Some("disk") => Prefer::Disk,
+                    _ => return Err(USAGE.into()),
+                }
+            }
+            path if dir.is_none() => dir = Some(PathBuf::from(path)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let dir = dir.ok_or(USAGE)?;
+    loop {
+        match sync_dir(service, &dir, prefer) {
+            Ok(report) => print_report(&report),
+            // the next pass tries again, a busy database shouldn't end the watch
+            Err(err) if watch => eprintln!("sync failed: {}", err),
+            Err(err) => return Err(err.into()),
+        }
+        if !watch {
+            return Ok(());
+        }
+        thread::sleep(SYNC_INTERVAL);
+    }
+}
+
+/// `template list|show|add|remove|use`, on the templates directory from the config
+fn run_template(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let templates = Config::load()?.templates();
+    let name = args.get(1).ok_or(USAGE);
+    match (args.first().map(String::as_str), args.len()) {
+        (Some("list"), 1) => {
+            for name in templates.list()? {
+                println!("{}", name);
+            }
+        }
+        (Some("show"), 2) => print!("{}", templates.get(name?)?),
+        (Some("add"), 2 | 3) => {
+            // the body comes from the file or, without one, from stdin
+            let body = match args.get(2) {
+                Some(file) => fs::read_to_string(file)?,
+                None => io::read_to_string(io::stdin())?,
+            };
+            templates.save(name?, &body)?;
+            println!("saved template {}", name?);
+        }
+        (Some("remove"), 2) => templates.remove(name?)?,
+        (Some("use"), 3..) => {
+            let title = args[2..].join(" ");
+            let expanded = expand_template(&templates.get(name?)?, &title, chrono::Local::now());
+            let mut service = get_note_service()?;
+            let ids = service.create_notes(vec![Note::new(None, title, expanded.text)])?;
+            print!("{}", service.fetch_note_by_id(ids[0])?);
+        }
+        _ => return Err(USAGE.into()),
+    }
+    Ok(())
+}
+
+/// opens today's note in vim, creating it from the journal template first if needed
+fn run_today() -> Result<(), Box<dyn std::error::Error>> {
+    let template = Config::load()?.journal_template()?;
+    let mut service = get_note_service()?;
+    let daily = service.open_daily_note(chrono::Local::now().date_naive(), template.as_deref())?;
+    let note_id = daily.note.id.ok_or("the daily note has no id")?;
+    let text = edit_text_with_vim_at(&daily.note.text, daily.cursor)?;
+    if text != daily.note.text {
+        let version = daily.note.version;
+        let note = Note { text, ..daily.note };
+        service.update_note(note_id, version, note)?;
+    }
+    print!("{}", service.fetch_note_by_id(note_id)?);
+    Ok(())
+}
+
+/// runs a one-shot subcommand instead of the TUI
+fn run_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args[0].as_str() {
+        command @ ("encrypt" | "rekey") => return run_key_command(command),
+        "template" => return run_template(&args[1..]),
+        "today" => return run_today(),
+        _ => (),
+    }
+    let mut service = get_note_service()?;
+    let note_id = |arg: Option<&String>| -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(arg.ok_or(USAGE)?.parse()?)
+    };
+    let when = |rest: &[String]| -> Result<_, Box<dyn std::error::Error>> {
+        let input = rest.join(" ");
+        Ok(parse_when(&input, chrono::Utc::now()).ok_or(USAGE)?)
+    };
+    let path = |arg: Option<&String>| -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Ok(PathBuf::from(arg.ok_or(USAGE)?))
+    };
+    let note = match args[0].as_str() {
+        "rpc" => return Ok(rpc::serve(&mut service)?),
+        "sync" => return run_sync(&mut service, &args[1..]),
+        "attach" => {
+            let id = service.attach_file(note_id(args.get(1))?, &path(args.get(2))?)?;
+            println!("stored as attachment {}", id);
+            return Ok(());
+        }
+        "attachments" => {
+            for attachment in service.list_attachments(note_id(args.get(1))?)? {
+                println!(
+                    "{}\t{}\t{}",
+                    attachment.id.unwrap_or_default(),
+                    attachment.display_size(),
+                    attachment.name
+                );
+            }
+            return Ok(());
+        }
+        "extract" => {
+            return Ok(service.extract_attachment(note_id(args.get(1))?, &path(args.get(2))?)?)
+        }
+        "detach" => return Ok(service.remove_attachment(note_id(args.get(1))?)?),
+        "export" => {
+            let exported = service.export_notes()?;
+            fs::write(path(args.get(1))?, serde_json::to_string_pretty(&exported)?)?;
+            println!("exported {} notes", exported.len());
+            return Ok(());
+        }
+        "import" => {
+            let exported: Vec<ExportedNote> =
+                serde_json::from_str(&fs::read_to_string(path(args.get(1))?)?)?;
+            println!("imported {} notes", service.import_notes(exported)?.len());
+            return Ok(());
+        }
+        "due" => service.set_due(note_id(args.get(1))?, Some(when(&args[2..])?))?,
+        "remind" => service.set_reminder(note_id(args.get(1))?, Some(when(&args[2..])?))?,
+        "done" => service.complete(note_id(args.get(1))?)?,
+        _ => return Err(USAGE.into()),
+    };
+    print!("{}", note);
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return run_command(&args);
+    }
+    // a broken keymap or theme is reported before the terminal is taken over
+    let config = Config::load()?;
+    config.check()?;
     let (tx, rx) = mpsc::channel();
     let (tx2, rx2): (Sender<bool>, Receiver<bool>) = mpsc::channel();
     let tick_rate = Duration::from_millis(20);
@@ -53,15 +336,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = tx.send(Event::Tick) {
-                    last_tick = Instant::now();
-                }
+            if last_tick.elapsed() >= tick_rate && tx.send(Event::Tick).is_ok() {
+                last_tick = Instant::now();
             }
         }
     });
     let mut terminal = initialized_terminal()?;
-    let mut handler = get_handler(terminal.get_frame().size())?;
+    let mut handler = get_handler(terminal.get_frame().size(), config)?;
     loop {
         // let ref_active
         terminal.draw(|f| {
@@ -72,11 +353,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             cleanup_terminal(terminal)?;
             tx2.send(true)?;
             // io::stdout().flush().unwrap();
-            handler.handle_edit()?;
+            handler.handle_edit();
             terminal = initialized_terminal()?;
             tx2.send(false)?;
         }
-        handler.handle_event(event)?;
+        handler.handle_event(event);
         if handler.should_exit() {
             cleanup_terminal(terminal)?;
             break;
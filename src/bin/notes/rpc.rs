@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use smart_notes_cli::{Note, NoteError, NoteService};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// server-defined codes for errors from the notes themselves
+const NOTE_NOT_FOUND: i64 = -32001;
+const NOTE_CONFLICT: i64 = -32002;
+const NOTE_ERROR: i64 = -32000;
+
+/// how often changes made by other programs are looked for while the client is quiet
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const METHODS: [&str; 10] = [
+    "initialize",
+    "shutdown",
+    "notes/list",
+    "notes/get",
+    "notes/search",
+    "notes/create",
+    "notes/update",
+    "notes/delete",
+    "links/resolve",
+    "links/list",
+];
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<NoteError> for RpcError {
+    fn from(err: NoteError) -> Self {
+        let code = match err {
+            NoteError::NotFound(_) | NoteError::AttachmentNotFound(_) => NOTE_NOT_FOUND,
+            NoteError::Conflict { .. } => NOTE_CONFLICT,
+            NoteError::Invalid(_) => INVALID_PARAMS,
+            _ => NOTE_ERROR,
+        };
+        RpcError::new(code, err)
+    }
+}
+
+/// a note created, changed or deleted by this session or any other program
+enum Change {
+    Created(usize),
+    Changed(usize),
+    Deleted(usize),
+}
+
+impl Change {
+    fn notification(&self) -> Value {
+        let (method, id) = match self {
+            Change::Created(id) => ("notes/didCreate", id),
+            Change::Changed(id) => ("notes/didChange", id),
+            Change::Deleted(id) => ("notes/didDelete", id),
+        };
+        json!({ "jsonrpc": "2.0", "method": method, "params": { "id": id } })
+    }
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: usize,
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+}
+
+#[derive(Deserialize)]
+struct CreateParams {
+    title: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// fields left out keep their current value
+#[derive(Deserialize)]
+struct UpdateParams {
+    id: usize,
+    /// version the client last read, the update fails if the note moved on since
+    version: Option<u64>,
+    title: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    target: String,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err))
+}
+
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|err| RpcError::new(NOTE_ERROR, err))
+}
+
+/// reads one `Content-Length` framed message, `None` at end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// the version of every note, a note's version moves on with each write to it
+fn versions(service: &NoteService) -> Result<BTreeMap<usize, u64>, NoteError> {
+    Ok(service
+        .list_all_notes()?
+        .into_iter()
+        .filter_map(|note| Some((note.id?, note.version)))
+        .collect())
+}
+
+struct Session<'a> {
+    service: &'a mut NoteService,
+    /// versions the client was last told about
+    known: BTreeMap<usize, u64>,
+    /// `PRAGMA data_version` when `known` was taken, moves when another program commits
+    data_version: u64,
+    /// a request wrote notes, which doesn't move this connection's data version
+    wrote: bool,
+    shutdown: bool,
+}
+
+impl Session<'_> {
+    /// everything that changed since the last call, in order of note id
+    fn changes(&mut self) -> Result<Vec<Change>, NoteError> {
+        let data_version = self.service.data_version()?;
+        if !self.wrote && data_version == self.data_version {
+            return Ok(Vec::new());
+        }
+        let now = versions(self.service)?;
+        let mut changes: Vec<Change> = now
+            .iter()
+            .filter_map(|(id, version)| match self.known.get(id) {
+                None => Some(Change::Created(*id)),
+                Some(known) if known != version => Some(Change::Changed(*id)),
+                Some(_) => None,
+            })
+            .collect();
+        changes.extend(
+            self.known
+                .keys()
+                .filter(|id| !now.contains_key(id))
+                .map(|id| Change::Deleted(*id)),
+        );
+        self.known = now;
+        self.data_version = data_version;
+        self.wrote = false;
+        Ok(changes)
+    }
+
+    fn call(&mut self, method: &str, args: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "serverInfo": { "name": "notes", "version": env!("CARGO_PKG_VERSION") },
+                "methods": METHODS,
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "notes/list" => {
+                let ListParams { offset, limit } = params(args)?;
+                let notes = self.service.list_all_notes()?;
+                let total = notes.len();
+                let notes: Vec<Note> = notes
+                    .into_iter()
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect();
+                Ok(json!({ "notes": to_value(notes)?, "total": total }))
+            }
+            "notes/get" => {
+                let IdParams { id } = params(args)?;
+                to_value(self.service.fetch_note_by_id(id)?)
+            }
+            "notes/search" => {
+                let SearchParams { query } = params(args)?;
+                to_value(self.service.search_notes(&query)?)
+            }
+            "notes/create" => {
+                let CreateParams { title, text } = params(args)?;
+                if title.trim().is_empty() {
+                    return Err(RpcError::new(INVALID_PARAMS, "title must not be empty"));
+                }
+                let ids = self
+                    .service
+                    .create_notes(vec![Note::new(None, title, text)])?;
+                self.wrote = true;
+                to_value(self.service.fetch_note_by_id(ids[0])?)
+            }
+            "notes/update" => {
+                let UpdateParams {
+                    id,
+                    version,
+                    title,
+                    text,
+                } = params(args)?;
+                let mut note = self.service.fetch_note_by_id(id)?;
+                let version = version.unwrap_or(note.version);
+                note.title = title.unwrap_or(note.title);
+                note.text = text.unwrap_or(note.text);
+                // a new title also rewrites the notes linking here, `changes` finds those too
+                self.service.update_note(id, version, note)?;
+                self.wrote = true;
+                to_value(self.service.fetch_note_by_id(id)?)
+            }
+            "notes/delete" => {
+                let IdParams { id } = params(args)?;
+                let note = self.service.delete_note(id)?;
+                self.wrote = true;
+                to_value(note)
+            }
+            "links/resolve" => {
+                let ResolveParams { target } = params(args)?;
+                to_value(self.service.resolve_link(&target)?)
+            }
+            "links/list" => {
+                let IdParams { id } = params(args)?;
+                Ok(json!({
+                    "links": self.service.links_of(id)?,
+                    "backlinks": to_value(self.service.backlinks_of(id)?)?,
+                }))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {method}"),
+            )),
+        }
+    }
+
+    /// answers one message, `None` for notifications
+    fn handle(&mut self, body: &[u8]) -> Option<Value> {
+        let message: Value = match serde_json::from_slice(body) {
+            Ok(message) => message,
+            Err(err) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, err))),
+        };
+        let id = message.get("id").cloned();
+        let method = match message.get("method").and_then(Value::as_str) {
+            Some(method) if message.get("jsonrpc") == Some(&json!("2.0")) => method,
+            _ => {
+                let err = RpcError::new(INVALID_REQUEST, "expected a JSON-RPC 2.0 request");
+                return Some(error_response(id.unwrap_or(Value::Null), err));
+            }
+        };
+        let args = message.get("params").cloned().unwrap_or(json!({}));
+        let result = if self.shutdown {
+            Err(RpcError::new(INVALID_REQUEST, "server is shutting down"))
+        } else {
+            self.call(method, args)
+        };
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => error_response(id, err),
+        })
+    }
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+/// serves JSON-RPC 2.0 with LSP-style framing on stdin and stdout until `exit` or end of input
+///
+/// changes from the TUI, the bot or other commands are notified within [`POLL_INTERVAL`]
+pub fn serve(service: &mut NoteService) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    let data_version = service.data_version().map_err(io::Error::other)?;
+    let known = versions(service).map_err(io::Error::other)?;
+    let mut session = Session {
+        service,
+        known,
+        data_version,
+        wrote: false,
+        shutdown: false,
+    };
+    // stdin is read on its own thread so other programs' changes go out while the client waits
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = io::stdin().lock();
+        loop {
+            let message = read_message(&mut input);
+            let last = !matches!(message, Ok(Some(_)));
+            if sender.send(message).is_err() || last {
+                break;
+            }
+        }
+    });
+    loop {
+        match messages.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => {
+                let body = match message? {
+                    Some(body) => body,
+                    None => break,
+                };
+                let is_exit = serde_json::from_slice::<Value>(&body)
+                    .is_ok_and(|message| message.get("method") == Some(&json!("exit")));
+                if is_exit {
+                    break;
+                }
+                if let Some(response) = session.handle(&body) {
+                    write_message(&mut output, &response)?;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        match session.changes() {
+            Ok(changes) => {
+                for change in changes {
+                    write_message(&mut output, &change.notification())?;
+                }
+            }
+            Err(err) => eprintln!("Error looking for changes: {err}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use std::{cmp::Reverse, path::PathBuf};
+
+use crate::Note;
+
+/// something the TUI does, bound to keys and runnable by name from the `:` line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Home,
+    Notes,
+    New,
+    Tasks,
+    Journal,
+    Today,
+    PreviousDay,
+    NextDay,
+    OpenDay,
+    OpenRecent,
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Edit,
+    Delete,
+    Schedule,
+    NextLink,
+    FollowLink,
+    Back,
+    NextTask,
+    ToggleTask,
+    NextAttachment,
+    OpenAttachment,
+    SaveAttachment,
+    FilterScope,
+    FilterOwner,
+    FilterTasks,
+    ToggleListedTask,
+    OpenTask,
+    QuickSwitcher,
+    CommandLine,
+    Help,
+    OpenEditor,
+    PickTemplate,
+    Cancel,
+}
+
+/// every action with the name it is run by and what it does
+pub const ACTIONS: &[(Action, &str, &str)] = &[
+    (Action::Quit, "quit", "leave the app"),
+    (Action::Home, "home", "go to the Home tab"),
+    (Action::Notes, "notes", "go to the Notes tab"),
+    (
+        Action::New,
+        "new",
+        "add a note, `:new <title>` skips the prompt",
+    ),
+    (Action::Tasks, "tasks", "go to the Tasks tab"),
+    (Action::Journal, "journal", "go to the Journal tab"),
+    (
+        Action::Today,
+        "today",
+        "open today's daily note, starting it if needed",
+    ),
+    (
+        Action::PreviousDay,
+        "prev-day",
+        "open the daily note before the one shown",
+    ),
+    (
+        Action::NextDay,
+        "next-day",
+        "open the daily note after the one shown",
+    ),
+    (
+        Action::OpenDay,
+        "open-day",
+        "open the daily note selected in the journal",
+    ),
+    (
+        Action::OpenRecent,
+        "open-recent",
+        "open the recently edited note selected on Home",
+    ),
+    (Action::Up, "up", "select the previous entry"),
+    (Action::Down, "down", "select the next entry"),
+    (Action::Top, "top", "select the first entry"),
+    (Action::Bottom, "bottom", "select the last entry"),
+    (Action::Edit, "edit", "edit the selected note in vim"),
+    (Action::Delete, "delete", "delete the selected note"),
+    (
+        Action::Schedule,
+        "schedule",
+        "set when the selected note is due",
+    ),
+    (Action::NextLink, "next-link", "select the next [[link]]"),
+    (
+        Action::FollowLink,
+        "follow-link",
+        "open the selected [[link]]",
+    ),
+    (
+        Action::Back,
+        "back",
+        "return to the note a link was followed from",
+    ),
+    (
+        Action::NextTask,
+        "next-item",
+        "select the next checklist item",
+    ),
+    (
+        Action::ToggleTask,
+        "toggle-item",
+        "check or uncheck the selected item",
+    ),
+    (
+        Action::NextAttachment,
+        "next-attachment",
+        "select the next attachment",
+    ),
+    (
+        Action::OpenAttachment,
+        "open-attachment",
+        "open the selected attachment",
+    ),
+    (
+        Action::SaveAttachment,
+        "save-attachment",
+        "save the selected attachment to a file",
+    ),
+    (
+        Action::FilterScope,
+        "filter-scope",
+        "cycle the scope filter",
+    ),
+    (
+        Action::FilterOwner,
+        "filter-owner",
+        "cycle the owner filter",
+    ),
+    (
+        Action::FilterTasks,
+        "filter-tasks",
+        "cycle open/done/all tasks",
+    ),
+    (
+        Action::ToggleListedTask,
+        "toggle-task",
+        "check or uncheck the selected task",
+    ),
+    (
+        Action::OpenTask,
+        "open-task",
+        "open the note holding the selected task",
+    ),
+    (Action::QuickSwitcher, "switch", "jump to a note by title"),
+    (Action::CommandLine, "command", "open the : command line"),
+    (Action::Help, "help", "list the keys that work here"),
+    (
+        Action::OpenEditor,
+        "write",
+        "write the new note's text in vim",
+    ),
+    (
+        Action::PickTemplate,
+        "pick-template",
+        "choose a template for the new note",
+    ),
+    (Action::Cancel, "cancel", "go back without adding the note"),
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, ..)| *action == self)
+            .map_or("", |(_, name, _)| name)
+    }
+    pub fn description(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, ..)| *action == self)
+            .map_or("", |(.., description)| description)
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, known, _)| *known == name)
+            .map(|(action, ..)| *action)
+    }
+}
+
+/// how the Notes list is ordered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteSort {
+    /// oldest first
+    Created,
+    Updated,
+    Title,
+    Due,
+}
+
+impl NoteSort {
+    pub const NAMES: [&'static str; 4] = ["created", "updated", "title", "due"];
+
+    pub fn sort(self, notes: &mut [Note]) {
+        match self {
+            NoteSort::Created => notes.sort_by_key(|note| (note.created_at, note.id)),
+            // most recent first, notes never stamped last
+            NoteSort::Updated => notes.sort_by_key(|note| Reverse(note.updated_at)),
+            NoteSort::Title => notes.sort_by_key(|note| note.title.to_lowercase()),
+            NoteSort::Due => notes.sort_by_key(|note| (note.due_at.is_none(), note.due_at)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// one Markdown file per note with front-matter, as `notes sync` writes
+    Markdown,
+    /// everything `notes export` writes
+    Json,
+}
+
+/// a parsed `:` line
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Run(Action),
+    New(String),
+    Tag {
+        add: bool,
+        tag: String,
+    },
+    Sort(NoteSort),
+    Export {
+        format: ExportFormat,
+        path: PathBuf,
+    },
+    Theme(String),
+    /// opens the template in the editor, creating it if needed
+    EditTemplate(String),
+    RemoveTemplate(String),
+}
+
+/// commands taking arguments, completed alongside the action names
+const COMMANDS: [&str; 6] = ["tag", "sort", "export", "theme", "template", "q"];
+
+/// `~/out` to `$HOME/out`
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim().trim_start_matches(':');
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let command = match (name, args.as_slice()) {
+            ("", _) => return Err("type a command, Tab completes".to_string()),
+            ("q", []) => Command::Run(Action::Quit),
+            ("new", []) => Command::Run(Action::New),
+            ("new", _) => Command::New(rest.trim().to_string()),
+            ("tag", ["add", tag]) => Command::Tag {
+                add: true,
+                tag: tag.to_string(),
+            },
+            ("tag", ["remove" | "rm", tag]) => Command::Tag {
+                add: false,
+                tag: tag.to_string(),
+            },
+            ("tag", _) => return Err("usage: tag add|remove <tag>".to_string()),
+            ("sort", [key]) => Command::Sort(match *key {
+                "created" => NoteSort::Created,
+                "updated" => NoteSort::Updated,
+                "title" => NoteSort::Title,
+                "due" => NoteSort::Due,
+                _ => return Err(format!("usage: sort {}", NoteSort::NAMES.join("|"))),
+            }),
+            ("sort", _) => return Err(format!("usage: sort {}", NoteSort::NAMES.join("|"))),
+            ("export", [format, path]) => Command::Export {
+                format: match *format {
+                    "md" | "markdown" => ExportFormat::Markdown,
+                    "json" => ExportFormat::Json,
+                    _ => return Err("usage: export md <dir> | export json <file>".to_string()),
+                },
+                path: expand_home(path),
+            },
+            ("export", _) => return Err("usage: export md <dir> | export json <file>".to_string()),
+            ("theme", [theme]) => Command::Theme(theme.to_string()),
+            ("theme", _) => return Err("usage: theme <name>".to_string()),
+            ("template", ["edit", name]) => Command::EditTemplate(name.to_string()),
+            ("template", ["remove" | "rm", name]) => Command::RemoveTemplate(name.to_string()),
+            ("template", _) => return Err("usage: template edit|remove <name>".to_string()),
+            (name, []) => Command::Run(
+                Action::from_name(name).ok_or_else(|| format!("unknown command `{}`", name))?,
+            ),
+            (name, _) => return Err(format!("`{}` takes no arguments", name)),
+        };
+        Ok(command)
+    }
+}
+
+/// full lines the partial `line` could be completed to
+pub fn complete(line: &str, themes: &[&str]) -> Vec<String> {
+    let (name, rest) = match line.split_once(' ') {
+        Some((name, rest)) => (name, rest),
+        None => {
+            let mut names: Vec<String> = ACTIONS
+                .iter()
+                .map(|(_, name, _)| *name)
+                .chain(COMMANDS)
+                .filter(|name| name.starts_with(line))
+                .map(str::to_string)
+                .collect();
+            names.sort();
+            names.dedup();
+            return names;
+        }
+    };
+    let words: &[&str] = match (name, rest.split_once(' ')) {
+        ("tag", None) => &["add", "remove"],
+        ("sort", None) => &NoteSort::NAMES,
+        ("export", None) => &["md", "json"],
+        ("theme", None) => themes,
+        ("template", None) => &["edit", "remove"],
+        _ => &[],
+    };
+    words
+        .iter()
+        .filter(|word| word.starts_with(rest))
+        .map(|word| format!("{} {}", name, word))
+        .collect()
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::error::NoteResult;
+use crate::keymap::{Keymap, KeymapConfig, Preset};
+use crate::template::TemplateDir;
+use crate::theme::{self, Theme};
+
+/// read when `NOTES_CONFIG` doesn't name another file
+const DEFAULT_CONFIG_PATH: &str = "notes.json";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("could not parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("{0} is not valid: {1}")]
+    Env(&'static str, String),
+    #[error("invalid keymap in {0}: {1}")]
+    Keymap(PathBuf, String),
+    #[error("{0}")]
+    Theme(String),
+}
+
+/// TUI settings from the config file, with environment variables taking precedence
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub keymap: KeymapConfig,
+    /// a built-in theme or the name of a file in `themes_dir`, `dark` when unset
+    pub theme: Option<String>,
+    /// where `<name>.json` theme files are looked up, `themes` next to the config by default
+    pub themes_dir: Option<PathBuf>,
+    /// where `<name>.md` note templates are kept, `templates` next to the config by default
+    pub templates_dir: Option<PathBuf>,
+    /// template new daily notes start from, blank when unset
+    pub journal_template: Option<String>,
+    /// file the config was read from
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Config {
+    /// reads `NOTES_CONFIG` (or `notes.json` if present) and applies the environment
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = env::var("NOTES_CONFIG").ok().map(PathBuf::from);
+        let mut config = match path {
+            Some(path) => Config::read(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Config::read(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Config {
+                path: PathBuf::from(DEFAULT_CONFIG_PATH),
+                ..Config::default()
+            },
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Config, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|err| ConfigError::Io(path.into(), err))?;
+        let mut config: Config =
+            serde_json::from_str(&content).map_err(|err| ConfigError::Parse(path.into(), err))?;
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Ok(theme) = env::var("NOTES_THEME") {
+            self.theme = Some(theme.trim().to_string());
+        }
+        if let Ok(preset) = env::var("NOTES_KEYMAP") {
+            self.keymap.preset = match preset.trim() {
+                "default" => Preset::Default,
+                "vim" => Preset::Vim,
+                "emacs" => Preset::Emacs,
+                other => {
+                    return Err(ConfigError::Env(
+                        "NOTES_KEYMAP",
+                        format!("unknown preset {other:?}, expected default, vim or emacs"),
+                    ))
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// the configured bindings, failing on any that conflict
+    pub fn keymap(&self) -> Result<Keymap, ConfigError> {
+        Keymap::from_config(&self.keymap).map_err(|err| ConfigError::Keymap(self.path.clone(), err))
+    }
+
+    /// `dir` relative to the config file
+    fn beside(&self, dir: &str) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(dir)
+    }
+    pub fn themes_dir(&self) -> PathBuf {
+        self.themes_dir
+            .clone()
+            .unwrap_or_else(|| self.beside("themes"))
+    }
+    pub fn templates(&self) -> TemplateDir {
+        TemplateDir::new(
+            self.templates_dir
+                .clone()
+                .unwrap_or_else(|| self.beside("templates")),
+        )
+    }
+
+    /// body of the journal template, `None` when none is configured
+    pub fn journal_template(&self) -> NoteResult<Option<String>> {
+        self.journal_template
+            .as_deref()
+            .map(|name| self.templates().get(name))
+            .transpose()
+    }
+
+    /// the configured theme, with colors dropped when `NO_COLOR` is set
+    pub fn theme(&self) -> Result<Theme, ConfigError> {
+        let name = self.theme.as_deref().unwrap_or(theme::BUILT_IN[0]);
+        Theme::load(name, &self.themes_dir()).map_err(ConfigError::Theme)
+    }
+
+    /// fails on anything the TUI would refuse to start with
+    pub fn check(&self) -> Result<(), ConfigError> {
+        self.keymap()?;
+        self.theme()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+
+use crate::error::{NoteError, NoteResult};
+
+/// marks a stored value as ciphertext, anything else is rejected outside a migration
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// random key that encrypts note fields, stored wrapped by the passphrase key
+pub struct Cipher {
+    aead: XChaCha20Poly1305,
+    /// plaintext written before encryption was enabled is passed through instead of rejected
+    migrating: bool,
+}
+
+/// what the database keeps to unlock the data key again
+pub struct WrappedKey {
+    pub salt: Vec<u8>,
+    pub wrapped: Vec<u8>,
+}
+
+fn passphrase_key(passphrase: &str, salt: &[u8]) -> NoteResult<XChaCha20Poly1305> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| NoteError::Crypto(err.to_string()))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+fn seal(aead: &XChaCha20Poly1305, plaintext: &[u8]) -> NoteResult<Vec<u8>> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        aead.encrypt(&nonce, plaintext)
+            .map_err(|err| NoteError::Crypto(err.to_string()))?,
+    );
+    Ok(sealed)
+}
+
+fn open(aead: &XChaCha20Poly1305, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    aead.decrypt(XNonce::from_slice(nonce), ciphertext).ok()
+}
+
+impl Cipher {
+    /// creates a fresh data key and wraps it with the passphrase
+    pub fn generate(passphrase: &str) -> NoteResult<(Cipher, WrappedKey)> {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        let cipher = Cipher {
+            aead: XChaCha20Poly1305::new(&key.into()),
+            migrating: false,
+        };
+        let wrapped = Cipher::wrap(&key, passphrase)?;
+        Ok((cipher, wrapped))
+    }
+
+    /// unwraps the data key, failing with [`NoteError::WrongPassphrase`] without touching data
+    pub fn unlock(passphrase: &str, wrapped: &WrappedKey) -> NoteResult<Cipher> {
+        let key = open(
+            &passphrase_key(passphrase, &wrapped.salt)?,
+            &wrapped.wrapped,
+        )
+        .filter(|key| key.len() == KEY_LEN)
+        .ok_or(NoteError::WrongPassphrase)?;
+        Ok(Cipher {
+            aead: XChaCha20Poly1305::new_from_slice(&key)
+                .map_err(|err| NoteError::Crypto(err.to_string()))?,
+            migrating: false,
+        })
+    }
+
+    /// wraps the same data key under a new passphrase, notes stay as they are
+    pub fn rekey(old: &str, new: &str, wrapped: &WrappedKey) -> NoteResult<WrappedKey> {
+        let key = open(&passphrase_key(old, &wrapped.salt)?, &wrapped.wrapped)
+            .ok_or(NoteError::WrongPassphrase)?;
+        Cipher::wrap(&key, new)
+    }
+
+    fn wrap(key: &[u8], passphrase: &str) -> NoteResult<WrappedKey> {
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let wrapped = seal(&passphrase_key(passphrase, &salt)?, key)?;
+        Ok(WrappedKey { salt, wrapped })
+    }
+
+    pub fn set_migrating(&mut self, migrating: bool) {
+        self.migrating = migrating;
+    }
+
+    fn plaintext<T>(&self, stored: T) -> NoteResult<T> {
+        if self.migrating {
+            Ok(stored)
+        } else {
+            Err(NoteError::Crypto(
+                "found an unencrypted value in encrypted notes".into(),
+            ))
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> NoteResult<String> {
+        Ok(format!(
+            "{}{}",
+            PREFIX,
+            to_hex(&seal(&self.aead, plaintext.as_bytes())?)
+        ))
+    }
+
+    pub fn encrypt_bytes(&self, plaintext: &[u8]) -> NoteResult<Vec<u8>> {
+        let mut sealed = PREFIX.as_bytes().to_vec();
+        sealed.extend(seal(&self.aead, plaintext)?);
+        Ok(sealed)
+    }
+
+    /// decrypts stored bytes, data without the prefix only passes while migrating
+    pub fn decrypt_bytes(&self, stored: &[u8]) -> NoteResult<Vec<u8>> {
+        match stored.strip_prefix(PREFIX.as_bytes()) {
+            Some(sealed) => open(&self.aead, sealed)
+                .ok_or_else(|| NoteError::Crypto("ciphertext failed authentication".into())),
+            None => self.plaintext(stored.to_vec()),
+        }
+    }
+
+    /// decrypts a stored value, plaintext only passes while migrating
+    pub fn decrypt(&self, stored: &str) -> NoteResult<String> {
+        let sealed = match stored.strip_prefix(PREFIX) {
+            Some(hex) => from_hex(hex).ok_or_else(|| NoteError::Crypto("bad ciphertext".into()))?,
+            None => return self.plaintext(stored.to_string()),
+        };
+        let plaintext = open(&self.aead, &sealed)
+            .ok_or_else(|| NoteError::Crypto("ciphertext failed authentication".into()))?;
+        String::from_utf8(plaintext).map_err(|err| NoteError::Crypto(err.to_string()))
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` alone would also take a sign such as `+f`
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values_with_a_fresh_nonce_each_time() {
+        let (cipher, _) = Cipher::generate("secret").unwrap();
+        let first = cipher.encrypt("shopping list").unwrap();
+        let second = cipher.encrypt("shopping list").unwrap();
+        assert!(first.starts_with(PREFIX) && !first.contains("shopping"));
+        assert_ne!(first, second);
+        assert_eq!(cipher.decrypt(&first).unwrap(), "shopping list");
+
+        let sealed = cipher.encrypt_bytes(b"\x00binary").unwrap();
+        assert_eq!(cipher.decrypt_bytes(&sealed).unwrap(), b"\x00binary");
+    }
+
+    #[test]
+    fn unlock_needs_the_passphrase_the_key_was_wrapped_with() {
+        let (cipher, wrapped) = Cipher::generate("secret").unwrap();
+        let stored = cipher.encrypt("note").unwrap();
+        assert!(matches!(
+            Cipher::unlock("Secret", &wrapped),
+            Err(NoteError::WrongPassphrase)
+        ));
+        let unlocked = Cipher::unlock("secret", &wrapped).unwrap();
+        assert_eq!(unlocked.decrypt(&stored).unwrap(), "note");
+    }
+
+    #[test]
+    fn rekey_keeps_the_data_key_and_drops_the_old_passphrase() {
+        let (cipher, wrapped) = Cipher::generate("old").unwrap();
+        let stored = cipher.encrypt("note").unwrap();
+        assert!(matches!(
+            Cipher::rekey("wrong", "new", &wrapped),
+            Err(NoteError::WrongPassphrase)
+        ));
+        let rewrapped = Cipher::rekey("old", "new", &wrapped).unwrap();
+        assert_ne!(rewrapped.salt, wrapped.salt);
+        assert!(matches!(
+            Cipher::unlock("old", &rewrapped),
+            Err(NoteError::WrongPassphrase)
+        ));
+        let unlocked = Cipher::unlock("new", &rewrapped).unwrap();
+        assert_eq!(unlocked.decrypt(&stored).unwrap(), "note");
+    }
+
+    #[test]
+    fn rejects_plaintext_unless_migrating_and_tampered_ciphertext_always() {
+        let (mut cipher, _) = Cipher::generate("secret").unwrap();
+        assert!(matches!(cipher.decrypt("plain"), Err(NoteError::Crypto(_))));
+        assert!(matches!(
+            cipher.decrypt_bytes(b"plain"),
+            Err(NoteError::Crypto(_))
+        ));
+        cipher.set_migrating(true);
+        assert_eq!(cipher.decrypt("plain").unwrap(), "plain");
+        assert_eq!(cipher.decrypt_bytes(b"plain").unwrap(), b"plain");
+
+        let mut stored = cipher.encrypt("note").unwrap();
+        let last = stored.pop().unwrap();
+        stored.push(if last == '0' { '1' } else { '0' });
+        assert!(matches!(cipher.decrypt(&stored), Err(NoteError::Crypto(_))));
+        assert!(cipher.decrypt(&format!("{PREFIX}zz")).is_err());
+    }
+
+    #[test]
+    fn hex_round_trips_and_rejects_malformed_input() {
+        let bytes = [0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(to_hex(&bytes), "000fa5ff");
+        assert_eq!(from_hex("000fa5ff").unwrap(), bytes);
+        assert_eq!(from_hex("000FA5FF").unwrap(), bytes);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+        for bad in ["abc", "zz", "é", "+1"] {
+            assert_eq!(from_hex(bad), None, "{bad:?}");
+        }
+    }
+}
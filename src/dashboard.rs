@@ -0,0 +1,114 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use crate::{tags, tasks, Note};
+
+/// days shown in the notes-created chart, today last
+const WEEK: usize = 7;
+/// most tags, recent notes and reminders listed on the dashboard
+const TOP: usize = 5;
+
+/// counts and short lists shown on the Home tab
+#[derive(Clone, Debug, Default)]
+pub struct Dashboard {
+    pub total_notes: usize,
+    /// notes created on each of the last seven local days, oldest first
+    pub created_per_day: Vec<(NaiveDate, u64)>,
+    /// tags by how many notes carry them, most used first
+    pub top_tags: Vec<(String, usize)>,
+    /// most recently edited first
+    pub recent: Vec<Note>,
+    pub open_tasks: usize,
+    /// pending reminders, soonest first
+    pub upcoming: Vec<Note>,
+}
+
+impl Dashboard {
+    /// `total_notes` and `created` come from the store's counts, `notes` is scanned only for
+    /// what needs their text or a sorted pick
+    pub fn new(
+        total_notes: usize,
+        created: &[(NaiveDate, u64)],
+        notes: &[Note],
+        now: DateTime<Utc>,
+    ) -> Dashboard {
+        Dashboard {
+            total_notes,
+            created_per_day: created_per_day(created, now),
+            top_tags: top_tags(notes),
+            recent: recently_edited(notes),
+            open_tasks: notes
+                .iter()
+                .flat_map(tasks::parse_tasks)
+                .filter(|task| !task.done)
+                .count(),
+            upcoming: upcoming_reminders(notes),
+        }
+    }
+}
+
+fn week_days(now: DateTime<Utc>) -> impl DoubleEndedIterator<Item = NaiveDate> {
+    let today = now.with_timezone(&Local).date_naive();
+    (0..WEEK)
+        .rev()
+        .map(move |ago| today - Duration::days(ago as i64))
+}
+
+/// local midnight starting the oldest day of the chart, the earliest instant
+/// [`NoteStore::count_created_per_day`](crate::NoteStore::count_created_per_day) needs
+pub fn week_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    let first = now.with_timezone(&Local).date_naive() - Duration::days(WEEK as i64 - 1);
+    let midnight = first.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |start| start.with_timezone(&Utc))
+}
+
+/// fills in the days without notes so the chart always has the whole week
+fn created_per_day(created: &[(NaiveDate, u64)], now: DateTime<Utc>) -> Vec<(NaiveDate, u64)> {
+    week_days(now)
+        .map(|day| {
+            let count = created
+                .iter()
+                .find(|(known, _)| *known == day)
+                .map_or(0, |(_, count)| *count);
+            (day, count)
+        })
+        .collect()
+}
+
+fn top_tags(notes: &[Note]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for tag in notes.iter().flat_map(|note| tags::parse_tags(&note.text)) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    counts.truncate(TOP);
+    counts
+}
+
+/// notes never stamped with an edit time are left out
+fn recently_edited(notes: &[Note]) -> Vec<Note> {
+    let mut recent: Vec<Note> = notes
+        .iter()
+        .filter(|note| note.updated_at.is_some())
+        .cloned()
+        .collect();
+    recent.sort_by_key(|note| Reverse(note.updated_at));
+    recent.truncate(TOP);
+    recent
+}
+
+fn upcoming_reminders(notes: &[Note]) -> Vec<Note> {
+    let mut upcoming: Vec<Note> = notes
+        .iter()
+        .filter(|note| note.remind_at.is_some() && !note.completed)
+        .cloned()
+        .collect();
+    upcoming.sort_by_key(|note| note.remind_at);
+    upcoming.truncate(TOP);
+    upcoming
+}
@@ -1,18 +1,42 @@
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::process::Command;
 use tempfile::NamedTempFile;
 
-pub fn edit_with_vim() -> Result<String, Box<dyn std::error::Error>> {
+/// opens vim on a copy of `initial` and returns what was saved
+pub fn edit_text_with_vim(initial: &str) -> Result<String, Box<dyn std::error::Error>> {
+    edit_text_with_vim_at(initial, None)
+}
+
+/// like [`edit_text_with_vim`], starting at a 1-based line and column
+pub fn edit_text_with_vim_at(
+    initial: &str,
+    cursor: Option<(usize, usize)>,
+) -> Result<String, Box<dyn std::error::Error>> {
     // Create a temporary file.
-    let file = NamedTempFile::new()?;
-    // writeln!(file, "Type your text here...")?;
+    let mut file = NamedTempFile::new()?;
+    file.write_all(initial.as_bytes())?;
+    file.flush()?;
 
     // Get the path of the temporary file.
     let file_path = file.path().to_str().unwrap_or_default().to_string();
 
     // Launch Vim on the file.
-    Command::new("vim").arg(&file_path).status()?;
+    let mut vim = Command::new("vim");
+    if let Some((line, column)) = cursor {
+        // typing starts there, appending when that is past the end of the line
+        let line_len = initial.lines().nth(line - 1).map_or(0, str::len);
+        let insert = if column > line_len {
+            "startinsert!"
+        } else {
+            "startinsert"
+        };
+        vim.arg(format!("+call cursor({}, {})", line, column))
+            .arg(format!("+{}", insert));
+    }
+    vim.arg(&file_path)
+        .status()
+        .map_err(|err| format!("could not start vim: {}", err))?;
     // Read the contents of the file back into a String.
     let mut contents = String::new();
     let mut file = File::open(file_path)?;
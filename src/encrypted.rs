@@ -0,0 +1,186 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{
+    attachment::Attachment,
+    crypto::Cipher,
+    error::{NoteError, NoteResult},
+    links,
+    store::{self, NoteStore},
+    Note,
+};
+
+/// wraps another store, encrypting titles, texts and link targets before they reach it
+pub struct EncryptedStore {
+    inner: Box<dyn NoteStore>,
+    cipher: Cipher,
+}
+
+impl EncryptedStore {
+    pub fn new(inner: Box<dyn NoteStore>, cipher: Cipher) -> EncryptedStore {
+        EncryptedStore { inner, cipher }
+    }
+
+    /// the wrapped store, for giving up on encrypting it
+    pub fn into_inner(self) -> Box<dyn NoteStore> {
+        self.inner
+    }
+
+    /// lets rows written before encryption was enabled be read while they are re-encrypted
+    pub fn set_migrating(&mut self, migrating: bool) {
+        self.cipher.set_migrating(migrating);
+    }
+
+    fn seal(&self, mut note: Note) -> NoteResult<Note> {
+        note.title = self.cipher.encrypt(&note.title)?;
+        note.text = self.cipher.encrypt(&note.text)?;
+        Ok(note)
+    }
+
+    fn open(&self, mut note: Note) -> NoteResult<Note> {
+        note.title = self.cipher.decrypt(&note.title)?;
+        note.text = self.cipher.decrypt(&note.text)?;
+        Ok(note)
+    }
+
+    fn open_all(&self, notes: Vec<Note>) -> NoteResult<Vec<Note>> {
+        notes.into_iter().map(|note| self.open(note)).collect()
+    }
+}
+
+impl NoteStore for EncryptedStore {
+    fn initialize_db(&mut self) -> NoteResult<()> {
+        self.inner.initialize_db()
+    }
+
+    fn get_notes(&self) -> NoteResult<Vec<Note>> {
+        self.open_all(self.inner.get_notes()?)
+    }
+
+    fn get_note(&self, note_id: usize) -> NoteResult<Note> {
+        self.open(self.inner.get_note(note_id)?)
+    }
+
+    fn add(&mut self, notes: Vec<Note>) -> NoteResult<Vec<usize>> {
+        let notes = notes
+            .into_iter()
+            .map(|note| self.seal(note))
+            .collect::<NoteResult<Vec<_>>>()?;
+        self.inner.add(notes)
+    }
+
+    fn update(&mut self, note_id: usize, new_note: Note) -> NoteResult<()> {
+        let new_note = self.seal(new_note)?;
+        self.inner.update(note_id, new_note)
+    }
+
+    fn delete(&mut self, note_id: usize) -> NoteResult<Note> {
+        let note = self.inner.delete(note_id)?;
+        self.open(note)
+    }
+
+    /// ciphertext can't be matched by the backend, so this filters decrypted notes
+    fn search(&self, query: &str) -> NoteResult<Vec<Note>> {
+        Ok(self
+            .get_notes()?
+            .into_iter()
+            .filter(|note| store::matches_search(note, query))
+            .collect())
+    }
+
+    fn set_links(&mut self, note_id: usize, targets: &[String]) -> NoteResult<()> {
+        let targets = targets
+            .iter()
+            .map(|target| self.cipher.encrypt(target))
+            .collect::<NoteResult<Vec<_>>>()?;
+        self.inner.set_links(note_id, &targets)
+    }
+
+    fn get_links(&self, note_id: usize) -> NoteResult<Vec<String>> {
+        self.inner
+            .get_links(note_id)?
+            .iter()
+            .map(|target| self.cipher.decrypt(target))
+            .collect()
+    }
+
+    fn get_backlinks(&self, note_id: usize) -> NoteResult<Vec<Note>> {
+        let note = self.get_note(note_id)?;
+        let mut backlinks = Vec::new();
+        for other in self.get_notes()? {
+            let other_id = other.id.ok_or(NoteError::NotFound(note_id))?;
+            if other_id != note_id
+                && self
+                    .get_links(other_id)?
+                    .iter()
+                    .any(|target| links::matches(target, note_id, &note.title))
+            {
+                backlinks.push(other);
+            }
+        }
+        Ok(backlinks)
+    }
+
+    fn get_due_reminders(&self, now: DateTime<Utc>) -> NoteResult<Vec<Note>> {
+        self.open_all(self.inner.get_due_reminders(now)?)
+    }
+
+    fn count_notes(&self) -> NoteResult<usize> {
+        self.inner.count_notes()
+    }
+
+    fn count_created_per_day(&self, since: DateTime<Utc>) -> NoteResult<Vec<(NaiveDate, u64)>> {
+        self.inner.count_created_per_day(since)
+    }
+
+    fn add_attachment(&mut self, mut attachment: Attachment) -> NoteResult<usize> {
+        attachment.name = self.cipher.encrypt(&attachment.name)?;
+        attachment.data = self.cipher.encrypt_bytes(&attachment.data)?;
+        self.inner.add_attachment(attachment)
+    }
+
+    fn get_attachments(&self, note_id: usize) -> NoteResult<Vec<Attachment>> {
+        self.inner
+            .get_attachments(note_id)?
+            .into_iter()
+            .map(|mut attachment| {
+                attachment.name = self.cipher.decrypt(&attachment.name)?;
+                Ok(attachment)
+            })
+            .collect()
+    }
+
+    fn get_attachment(&self, attachment_id: usize) -> NoteResult<Attachment> {
+        let mut attachment = self.inner.get_attachment(attachment_id)?;
+        attachment.name = self.cipher.decrypt(&attachment.name)?;
+        attachment.data = self.cipher.decrypt_bytes(&attachment.data)?;
+        Ok(attachment)
+    }
+
+    fn delete_attachment(&mut self, attachment_id: usize) -> NoteResult<()> {
+        self.inner.delete_attachment(attachment_id)
+    }
+
+    fn get_meta(&self, key: &str) -> NoteResult<Option<Vec<u8>>> {
+        self.inner.get_meta(key)
+    }
+
+    fn set_meta(&mut self, key: &str, value: &[u8]) -> NoteResult<()> {
+        self.inner.set_meta(key, value)
+    }
+
+    fn begin(&mut self) -> NoteResult<()> {
+        self.inner.begin()
+    }
+
+    fn commit(&mut self) -> NoteResult<()> {
+        self.inner.commit()
+    }
+
+    fn rollback(&mut self) -> NoteResult<()> {
+        self.inner.rollback()
+    }
+
+    fn data_version(&self) -> NoteResult<u64> {
+        self.inner.data_version()
+    }
+}
@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+/// errors surfaced by the note storage layer
+#[derive(Debug, Error)]
+pub enum NoteError {
+    #[error("database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("note {0} not found")]
+    NotFound(usize),
+    #[error("attachment {0} not found")]
+    AttachmentNotFound(usize),
+    #[error("no template named `{0}`")]
+    TemplateNotFound(String),
+    #[error("only the owner can change who sees note {0}")]
+    Forbidden(usize),
+    #[error("note {id} was changed elsewhere (now at version {found}, expected {expected})")]
+    Conflict {
+        id: usize,
+        expected: u64,
+        found: u64,
+    },
+    #[error("invalid value: {0}")]
+    Invalid(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+    #[error("notes are encrypted, unlock them with a passphrase first")]
+    Locked,
+    #[error("encryption error: {0}")]
+    Crypto(String),
+}
+
+pub type NoteResult<T> = Result<T, NoteError>;
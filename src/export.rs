@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// a note with its attachments as written by `notes export`
+#[derive(Serialize, Deserialize)]
+pub struct ExportedNote {
+    pub id: Option<usize>,
+    pub title: String,
+    pub text: String,
+    #[serde(default)]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub remind_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// `local`, `private`, `guild:<id>` or `channel:<id>`
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub attachments: Vec<ExportedAttachment>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportedAttachment {
+    pub name: String,
+    /// hex encoded file contents
+    pub data: String,
+}
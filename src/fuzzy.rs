@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+
+use crate::Note;
+
+/// how many recently visited notes get a ranking boost
+pub const RECENT_LIMIT: usize = 20;
+/// most matches listed by the quick switcher
+const MAX_MATCHES: usize = 50;
+
+/// a query matched against one string
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    /// char indices of the candidate that matched the query
+    pub positions: Vec<usize>,
+}
+
+/// a note found by the quick switcher
+#[derive(Clone, Debug)]
+pub struct NoteMatch {
+    pub note: Note,
+    pub score: i64,
+    /// char indices of the title to highlight
+    pub positions: Vec<usize>,
+    /// body line containing the query, when the title itself did not match
+    pub snippet: Option<String>,
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    i == 0
+        || !chars[i - 1].is_alphanumeric()
+        || (chars[i - 1].is_lowercase() && chars[i].is_uppercase())
+}
+
+fn same_char(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// the query's chars all appear in order in `chars[from..]`
+fn matches_from(query: &[char], chars: &[char], from: usize) -> bool {
+    let mut rest = chars.iter().skip(from);
+    query.iter().all(|&q| rest.any(|&c| same_char(c, q)))
+}
+
+/// matches the query's characters in order anywhere in the candidate, ignoring case
+///
+/// consecutive runs and matches at the start of words score higher, gaps lower
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut positions: Vec<usize> = Vec::new();
+    let mut score = 0;
+    let mut from = 0;
+    for (n, &q) in query.iter().enumerate() {
+        let next = (from..chars.len()).find(|&i| same_char(chars[i], q))?;
+        // prefer a later word start over a mid-word hit, if the rest still matches after it
+        let i = match positions.last() {
+            Some(&last) if last + 1 == next => next,
+            _ => (next..chars.len())
+                .find(|&i| {
+                    same_char(chars[i], q)
+                        && is_word_start(&chars, i)
+                        && matches_from(&query[n + 1..], &chars, i + 1)
+                })
+                .unwrap_or(next),
+        };
+        score += 1;
+        match positions.last() {
+            Some(&last) if last + 1 == i => score += 5,
+            Some(&last) => score -= (i - last - 1).min(3) as i64,
+            None => score -= i.min(5) as i64,
+        }
+        if is_word_start(&chars, i) {
+            score += 8;
+        }
+        positions.push(i);
+        from = i + 1;
+    }
+    if positions.is_empty() {
+        return None;
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+/// ranks notes for the quick switcher, best first
+///
+/// title matches come first, then notes whose body contains the query. `recent`
+/// holds visited note ids, most recent last, and those notes rank first on ties and
+/// when the query is empty
+pub fn rank_notes(query: &str, notes: &[Note], recent: &[usize]) -> Vec<NoteMatch> {
+    let recency = |note: &Note| {
+        note.id
+            .and_then(|id| recent.iter().rev().position(|r| *r == id))
+            .map_or(0, |age| (RECENT_LIMIT - age.min(RECENT_LIMIT)) as i64)
+    };
+    let needle = query.trim().to_lowercase();
+    let mut matches: Vec<NoteMatch> = notes
+        .iter()
+        .filter_map(|note| {
+            if needle.is_empty() {
+                return Some(NoteMatch {
+                    note: note.clone(),
+                    score: recency(note),
+                    positions: Vec::new(),
+                    snippet: None,
+                });
+            }
+            if let Some(found) = fuzzy_match(&needle, &note.title) {
+                return Some(NoteMatch {
+                    note: note.clone(),
+                    score: found.score + recency(note),
+                    positions: found.positions,
+                    snippet: None,
+                });
+            }
+            let line = note
+                .text
+                .lines()
+                .find(|line| line.to_lowercase().contains(&needle))?;
+            Some(NoteMatch {
+                note: note.clone(),
+                score: recency(note),
+                positions: Vec::new(),
+                snippet: Some(line.trim().to_string()),
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        a.snippet
+            .is_some()
+            .cmp(&b.snippet.is_some())
+            .then(b.score.cmp(&a.score))
+            .then(if needle.is_empty() {
+                Ordering::Equal
+            } else {
+                a.note.title.len().cmp(&b.note.title.len())
+            })
+    });
+    matches.truncate(MAX_MATCHES);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: usize, title: &str, text: &str) -> Note {
+        Note::new(Some(id), title.into(), text.into())
+    }
+
+    fn titles(matches: &[NoteMatch]) -> Vec<&str> {
+        matches
+            .iter()
+            .map(|found| found.note.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn matches_characters_in_order_only() {
+        assert_eq!(fuzzy_match("sn", "smart notes").unwrap().positions, [0, 6]);
+        assert_eq!(fuzzy_match("nts", "notes").unwrap().positions, [0, 2, 4]);
+        assert!(fuzzy_match("ba", "abc").is_none());
+        assert!(fuzzy_match("xyz", "smart notes").is_none());
+        assert!(fuzzy_match(" ", "anything").is_none());
+    }
+
+    #[test]
+    fn ignores_case_and_spaces_in_the_query() {
+        assert_eq!(fuzzy_match("S N", "smart notes").unwrap().positions, [0, 6]);
+        assert_eq!(fuzzy_match("ÉT", "été").unwrap().positions, [0, 1]);
+        assert_eq!(
+            fuzzy_match("sn", "smart notes"),
+            fuzzy_match("SN", "Smart Notes")
+        );
+    }
+
+    #[test]
+    fn prefers_word_starts_and_consecutive_runs() {
+        assert_eq!(fuzzy_match("n", "ban notes").unwrap().positions, [4]);
+        assert_eq!(
+            fuzzy_match("qs", "quickSwitcher").unwrap().positions,
+            [0, 5]
+        );
+        let run = fuzzy_match("abc", "abcdef").unwrap().score;
+        let spread = fuzzy_match("abc", "axbxcx").unwrap().score;
+        assert!(run > spread, "{run} <= {spread}");
+    }
+
+    #[test]
+    fn ranks_titles_before_bodies_and_better_matches_first() {
+        let notes = [
+            note(1, "Groceries", "buy notebooks"),
+            note(2, "Meeting notes", ""),
+            note(3, "Notes", ""),
+            note(4, "Travel", ""),
+        ];
+        let ranked = rank_notes("notes", &notes, &[]);
+        assert_eq!(titles(&ranked), ["Notes", "Meeting notes"]);
+
+        let ranked = rank_notes("note", &notes, &[]);
+        assert_eq!(titles(&ranked), ["Notes", "Meeting notes", "Groceries"]);
+        assert_eq!(ranked[2].snippet.as_deref(), Some("buy notebooks"));
+    }
+
+    #[test]
+    fn recent_notes_come_first_for_an_empty_query_and_on_ties() {
+        let notes = [
+            note(1, "alpha", ""),
+            note(2, "beta", ""),
+            note(3, "gamma", ""),
+        ];
+        let ranked = rank_notes("", &notes, &[3, 1]);
+        assert_eq!(titles(&ranked), ["alpha", "gamma", "beta"]);
+
+        let twins = [note(1, "todo", ""), note(2, "todo", "")];
+        let ranked = rank_notes("todo", &twins, &[2]);
+        assert_eq!(ranked[0].note.id, Some(2));
+    }
+}
@@ -1,169 +1,1066 @@
 use std::io::Stdout;
+use std::time::{Duration, Instant};
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use tui::{backend::CrosstermBackend, layout::Rect, Frame};
+use tempfile::TempPath;
+use tui::{backend::CrosstermBackend, layout::Rect, widgets::Clear, Frame};
 
 use crate::{
-    app::{App, AppState},
-    editor_handler,
+    app::{App, AppState, CommandLine, EditConflict, QuickSwitcher, TemplatePicker, Toast},
+    attachment,
+    command::{Action, Command, ExportFormat},
+    editor_handler, journal,
+    keymap::{sequence_label, Key, Keymap, Lookup, Mode},
+    links,
     render::{self, Render},
-    repository::Repository,
+    schedule, sync, tasks,
+    template::{self, Expanded},
+    theme::{self, Theme},
     widgets::Widget,
-    Note,
+    Config, ConfigError, Note, NoteError, NoteService,
 };
 
+/// how often the database is checked for writes made by the bot or the CLI
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+/// how long a half typed key sequence like the first `g` of `gg` waits for the rest
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// handles the app
 pub struct AppHandler {
     app: App,
-    db: Repository,
+    db: NoteService,
     widget: Widget,
+    config: Config,
+    keymap: Keymap,
+    /// keys typed so far of a longer binding
+    pending_keys: Vec<Key>,
+    pending_since: Instant,
+    /// `PRAGMA data_version` when the lists were last loaded
+    data_version: u64,
+    last_refresh_check: Instant,
+    /// attachments handed to a viewer, deleted when the TUI exits
+    opened_attachments: Vec<TempPath>,
 }
 
 impl AppHandler {
-    pub fn new(app: App, db: Repository, size: Rect) -> AppHandler {
-        let widget = Widget::new(size);
-        AppHandler { app, db, widget }
+    pub fn new(
+        mut app: App,
+        db: NoteService,
+        size: Rect,
+        config: Config,
+    ) -> Result<AppHandler, ConfigError> {
+        let keymap = config.keymap()?;
+        let widget = Widget::new(size, config.theme()?);
+        if db.is_locked() {
+            app.set_state(AppState::Unlock);
+        }
+        let data_version = db.data_version().unwrap_or_default();
+        let mut handler = AppHandler {
+            app,
+            db,
+            widget,
+            config,
+            keymap,
+            pending_keys: Vec::new(),
+            pending_since: Instant::now(),
+            data_version,
+            last_refresh_check: Instant::now(),
+            opened_attachments: Vec::new(),
+        };
+        if !handler.db.is_locked() {
+            handler.set_dashboard();
+        }
+        Ok(handler)
+    }
+    /// reloads what is on screen when another process changed the notes, keeping the selection
+    fn refresh_if_changed(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.last_refresh_check.elapsed() < REFRESH_INTERVAL || self.db.is_locked() {
+            return Ok(());
+        }
+        self.last_refresh_check = Instant::now();
+        let version = self.db.data_version()?;
+        if version == self.data_version {
+            return Ok(());
+        }
+        self.data_version = version;
+        match self.app.state() {
+            AppState::Note | AppState::Schedule | AppState::SaveAttachment => {
+                let selected = self.app.selected_note().and_then(|note| note.id);
+                self.set_notes();
+                self.select_note_by_id(selected);
+            }
+            AppState::Tasks => self.set_tasks(),
+            AppState::Home => self.set_dashboard(),
+            AppState::Journal => self.set_journal(),
+            _ => (),
+        }
+        Ok(())
     }
     fn set_notes(&mut self) {
-        let notes = self.db.get_notes().expect("can't access db");
-        if notes.len() == 0 {
-            self.app.notes = None;
-        } else {
-            self.app.notes = Some(notes)
+        let mut notes = match self.db.list_all_notes() {
+            Ok(notes) => notes,
+            Err(err) => return self.app.error(format!("could not load notes: {}", err)),
+        };
+        self.app.note_count = notes.len();
+        notes.retain(|note| {
+            self.app
+                .scope_filter
+                .is_none_or(|kind| note.scope.kind() == kind)
+                && self
+                    .app
+                    .owner_filter
+                    .as_ref()
+                    .is_none_or(|owner| note.owner.as_ref() == Some(owner))
+        });
+        self.app.sort.sort(&mut notes);
+        self.app.set_notes(notes);
+        self.refresh_detail();
+    }
+    /// reloads the backlinks of the selected note and resets the link cursor
+    fn refresh_detail(&mut self) {
+        self.app.selected_link = None;
+        self.app.selected_task_line = None;
+        self.app.selected_attachment = None;
+        let detail = match self.app.selected_note().and_then(|note| note.id) {
+            Some(id) => self
+                .db
+                .backlinks_of(id)
+                .and_then(|backlinks| Ok((backlinks, self.db.list_attachments(id)?))),
+            None => Ok((Vec::new(), Vec::new())),
         };
+        match detail {
+            Ok(detail) => (self.app.backlinks, self.app.attachments) = detail,
+            Err(err) => self.app.error(format!("could not load links: {}", err)),
+        }
+    }
+    fn set_tasks(&mut self) {
+        match self.db.list_tasks(self.app.task_filter) {
+            Ok(tasks) => self.app.tasks = tasks,
+            Err(err) => return self.app.error(format!("could not load tasks: {}", err)),
+        }
+        let selected = match self.app.task_state().selected() {
+            _ if self.app.tasks.is_empty() => None,
+            Some(i) => Some(i.min(self.app.tasks.len() - 1)),
+            None => Some(0),
+        };
+        self.app.set_task_state(selected);
+    }
+    fn set_journal(&mut self) {
+        match self.db.journal() {
+            Ok(journal) => self.app.journal = journal,
+            Err(err) => {
+                return self
+                    .app
+                    .error(format!("could not load the journal: {}", err))
+            }
+        }
+        let selected = match self.app.journal_state().selected() {
+            _ if self.app.journal.is_empty() => None,
+            Some(i) => Some(i.min(self.app.journal.len() - 1)),
+            None => Some(0),
+        };
+        self.app.set_journal_state(selected);
+    }
+    fn set_dashboard(&mut self) {
+        match self.db.dashboard(chrono::Utc::now()) {
+            Ok(dashboard) => self.app.set_dashboard(dashboard),
+            Err(err) => self
+                .app
+                .error(format!("could not load the dashboard: {}", err)),
+        }
     }
     pub fn should_exit(&self) -> bool {
-        return self.app.should_exist;
+        self.app.should_exist
     }
     pub fn is_editor_mode(&self) -> bool {
-        return self.app.editor_mode;
+        self.app.editor_mode
     }
-    pub fn handle_event(
-        &mut self,
-        event: Event<KeyEvent>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    /// anything that fails shows up as an error in the status bar instead of ending the app
+    pub fn handle_event(&mut self, event: Event<KeyEvent>) {
+        if let Err(err) = self.dispatch(event) {
+            self.app.error(err.to_string());
+        }
+    }
+    fn dispatch(&mut self, event: Event<KeyEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Event::Tick = event {
+            if !self.pending_keys.is_empty() && self.pending_since.elapsed() > SEQUENCE_TIMEOUT {
+                self.pending_keys.clear();
+            }
+            if self.app.toast.as_ref().is_some_and(Toast::expired) {
+                self.app.toast = None;
+            }
+            self.refresh_if_changed()?
+        }
+        let tab = self.app.tab_state();
         match self.app.state() {
             AppState::Add => self.handle_adding(event)?,
+            AppState::Schedule => self.handle_scheduling(event)?,
+            AppState::Unlock => self.handle_unlock(event)?,
+            AppState::SaveAttachment => self.handle_save_attachment(event)?,
+            AppState::Conflict => self.handle_conflict(event)?,
+            AppState::Switcher => self.handle_switcher(event)?,
+            AppState::Command => self.handle_command_line(event)?,
+            AppState::Templates => self.handle_template_picker(event)?,
+            AppState::Help => {
+                // any key closes the popup
+                if let Event::Input(_) = event {
+                    let state = self.app.help.take().unwrap_or(AppState::Home);
+                    self.app.set_state(state);
+                }
+            }
             _ => self.handle_normal_input(event)?,
         };
+        // notes may have changed anywhere since the dashboard was last built
+        if tab != AppState::Home && self.app.tab_state() == AppState::Home {
+            self.set_dashboard();
+        }
         Ok(())
     }
-    pub fn handle_edit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// runs the editor for the note being edited or added, reporting failures in the status bar
+    pub fn handle_edit(&mut self) {
+        if let Err(err) = self.edit() {
+            self.app.editor_mode = false;
+            self.app.error(err.to_string());
+        }
+    }
+    fn edit(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(original) = self.app.editing.take() {
+            self.app.editor_mode = false;
+            let text = editor_handler::edit_text_with_vim(&original.text)?;
+            return self.save_edit(original, text);
+        }
+        if let Some(name) = self.app.editing_template.take() {
+            self.app.editor_mode = false;
+            let templates = self.config.templates();
+            let body = match templates.get(&name) {
+                Err(NoteError::TemplateNotFound(_)) => String::new(),
+                body => body?,
+            };
+            let body = editor_handler::edit_text_with_vim(&body)?;
+            templates.save(&name, &body)?;
+            self.app.success(format!("saved template `{}`", name));
+            return Ok(());
+        }
         let title = self.app.buffer.clone();
+        let start = match &self.app.template {
+            Some(name) => template::expand_template(
+                &self.config.templates().get(name)?,
+                &title,
+                chrono::Local::now(),
+            ),
+            None => Expanded {
+                text: String::new(),
+                cursor: None,
+            },
+        };
         self.app.buffer.clear();
-        let text = editor_handler::edit_with_vim()?;
-        let note = Note::new(None, title, text);
-        self.db.add(vec![note])?;
+        self.app.template = None;
+        let text = editor_handler::edit_text_with_vim_at(&start.text, start.cursor)?;
+        let note = Note::new(None, title.clone(), text);
+        let ids = self.db.create_notes(vec![note])?;
         self.app.editor_mode = false;
         self.app.set_state(AppState::Note);
+        self.set_notes();
+        self.select_note_by_id(ids.first().copied());
+        self.app.success(format!("added `{}`", title));
+        Ok(())
+    }
+    /// saves the edited text against the version editing started from
+    fn save_edit(
+        &mut self,
+        original: Note,
+        text: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let note_id = original.id.ok_or("this should not happened")?;
+        let version = original.version;
+        let mine = Note { text, ..original };
+        match self.db.update_note(note_id, version, mine.clone()) {
+            Ok(()) => {
+                self.app.conflict = None;
+                self.app.set_state(AppState::Note);
+                self.set_notes();
+                self.select_note_by_id(Some(note_id));
+                self.app.success(format!("saved `{}`", mine.title));
+            }
+            Err(NoteError::Conflict { .. }) => {
+                let theirs = self.db.fetch_note_by_id(note_id)?;
+                self.app.conflict = Some(EditConflict { mine, theirs });
+                self.app.set_state(AppState::Conflict);
+                self.app
+                    .warn("the note was saved elsewhere while you edited it");
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+    /// m keeps the edit over the other save, t drops it, e merges both in the editor
+    fn handle_conflict(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let code = match event {
+            Event::Input(event) => event.code,
+            Event::Tick => return Ok(()),
+        };
+        let conflict = match self.app.conflict.take() {
+            Some(conflict) => conflict,
+            None => {
+                self.app.set_state(AppState::Note);
+                return Ok(());
+            }
+        };
+        match code {
+            KeyCode::Char('m') => {
+                let text = conflict.mine.text.clone();
+                self.save_edit(conflict.theirs, text)?;
+            }
+            KeyCode::Char('t') => {
+                let note_id = conflict.theirs.id;
+                self.app.set_state(AppState::Note);
+                self.set_notes();
+                self.select_note_by_id(note_id);
+                self.app.success("kept the version saved elsewhere");
+            }
+            KeyCode::Char('e') => {
+                let merged = format!(
+                    "<<<<<<< mine\n{}\n=======\n{}\n>>>>>>> theirs\n",
+                    conflict.mine.text.trim_end(),
+                    conflict.theirs.text.trim_end()
+                );
+                self.app.editing = Some(Note {
+                    text: merged,
+                    ..conflict.theirs
+                });
+                self.app.editor_mode = true;
+            }
+            _ => self.app.conflict = Some(conflict),
+        }
+        Ok(())
+    }
+    /// typing narrows the matches, Up/Down or Ctrl-P/Ctrl-N pick one, Enter opens it
+    fn handle_switcher(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let event = match event {
+            Event::Input(event) => event,
+            Event::Tick => return Ok(()),
+        };
+        let switcher = match self.app.switcher.as_mut() {
+            Some(switcher) => switcher,
+            None => {
+                self.app.set_state(AppState::Home);
+                return Ok(());
+            }
+        };
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+        match event.code {
+            KeyCode::Esc => {
+                let return_to = switcher.return_to;
+                self.app.switcher = None;
+                self.app.set_state(return_to);
+            }
+            KeyCode::Enter => {
+                let note_id = switcher.selected().and_then(|found| found.note.id);
+                let return_to = switcher.return_to;
+                self.app.switcher = None;
+                match note_id {
+                    Some(note_id) => self.jump_to_note(note_id),
+                    None => self.app.set_state(return_to),
+                }
+            }
+            KeyCode::Up => switcher.move_selection(-1),
+            KeyCode::Down => switcher.move_selection(1),
+            KeyCode::Char('p') | KeyCode::Char('k') if control => switcher.move_selection(-1),
+            KeyCode::Char('n') | KeyCode::Char('j') if control => switcher.move_selection(1),
+            KeyCode::Char(c) => {
+                switcher.query.push(c);
+                switcher.update(&self.app.recent_notes);
+            }
+            KeyCode::Backspace => {
+                switcher.query.pop();
+                switcher.update(&self.app.recent_notes);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+    /// Up/Down choose, Enter picks, Esc keeps what was picked before
+    fn handle_template_picker(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let event = match event {
+            Event::Input(event) => event,
+            Event::Tick => return Ok(()),
+        };
+        let picker = match self.app.template_picker.as_mut() {
+            Some(picker) => picker,
+            None => {
+                self.app.set_state(AppState::Add);
+                return Ok(());
+            }
+        };
+        match event.code {
+            KeyCode::Up => picker.move_selection(-1),
+            KeyCode::Down => picker.move_selection(1),
+            KeyCode::Enter => {
+                self.app.template = picker.selected().cloned();
+                self.app.template_picker = None;
+                self.app.set_state(AppState::Add);
+            }
+            KeyCode::Esc => {
+                self.app.template_picker = None;
+                self.app.set_state(AppState::Add);
+            }
+            _ => (),
+        }
         Ok(())
     }
+    /// opens the Notes tab on this note, dropping list filters that hide it
+    fn jump_to_note(&mut self, note_id: usize) {
+        self.app.set_state(AppState::Note);
+        self.set_notes();
+        if !self.select_note_by_id(Some(note_id)) {
+            self.app.scope_filter = None;
+            self.app.owner_filter = None;
+            self.set_notes();
+            self.select_note_by_id(Some(note_id));
+        }
+    }
     fn handle_normal_input(
         &mut self,
         event: Event<KeyEvent>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char('q') => {
-                    self.app.should_exist = true;
+        let key = match event {
+            Event::Input(event) => Key::from_event(&event),
+            Event::Tick => return Ok(()),
+        };
+        self.pending_keys.push(key);
+        let mut found = self.keymap.lookup(*self.app.state(), &self.pending_keys);
+        if found == Lookup::Unbound && self.pending_keys.len() > 1 {
+            // the sequence went nowhere, the last key may still start or be a binding
+            self.pending_keys = vec![key];
+            found = self.keymap.lookup(*self.app.state(), &self.pending_keys);
+        }
+        match found {
+            Lookup::Run(action) => {
+                self.pending_keys.clear();
+                self.run_action(action)?;
+            }
+            Lookup::Pending => {
+                self.pending_since = Instant::now();
+                // the status bar shows the keys typed so far
+            }
+            Lookup::Unbound => self.pending_keys.clear(),
+        }
+        Ok(())
+    }
+    /// does what a key bound to `action` or its name on the `:` line asks for
+    fn run_action(&mut self, action: Action) -> Result<(), Box<dyn std::error::Error>> {
+        let mode = action.mode();
+        if mode != Mode::Global && mode != Mode::of(*self.app.state()) {
+            self.app.warn(format!(
+                "{} only works on the {} tab",
+                action.name(),
+                mode.name()
+            ));
+            return Ok(());
+        }
+        match action {
+            Action::Quit => self.app.should_exist = true,
+            Action::Home => self.app.set_state(AppState::Home),
+            Action::Notes => {
+                self.app.set_state(AppState::Note);
+                self.set_notes()
+            }
+            Action::New => self.app.set_state(AppState::Add),
+            Action::Tasks => {
+                self.app.set_state(AppState::Tasks);
+                self.set_tasks()
+            }
+            Action::Journal => {
+                self.app.set_state(AppState::Journal);
+                self.set_journal()
+            }
+            Action::Today => self.open_day(journal::today())?,
+            Action::PreviousDay => self.step_day(-1)?,
+            Action::NextDay => self.step_day(1)?,
+            Action::OpenDay => {
+                if let Some(note_id) = self.app.selected_day().and_then(|note| note.id) {
+                    self.jump_to_note(note_id);
                 }
-                KeyCode::Char('h') => self.app.set_state(AppState::Home),
-                KeyCode::Char('n') => {
-                    self.app.set_state(AppState::Note);
-                    self.set_notes()
+            }
+            Action::OpenRecent => {
+                if let Some(note_id) = self.app.selected_recent().and_then(|note| note.id) {
+                    self.jump_to_note(note_id);
                 }
-                KeyCode::Char('a') => self.app.set_state(AppState::Add),
-                KeyCode::Char('d') => {
-                    if self.app.is_state(AppState::Note) {
-                        self.handle_delete()?;
+            }
+            Action::Up | Action::Down => {
+                let step = if action == Action::Up { -1 } else { 1 };
+                if self.app.is_state(AppState::Tasks) {
+                    self.handle_task_move(step)
+                } else if self.app.is_state(AppState::Home) {
+                    self.handle_recent_move(step)
+                } else if self.app.is_state(AppState::Journal) {
+                    self.handle_day_move(step)
+                } else {
+                    self.handle_note_move(step);
+                    self.refresh_detail();
+                }
+            }
+            Action::Top | Action::Bottom => {
+                let last = action == Action::Bottom;
+                if self.app.is_state(AppState::Tasks) {
+                    let len = self.app.tasks.len();
+                    if len > 0 {
+                        self.app
+                            .set_task_state(Some(if last { len - 1 } else { 0 }));
+                    }
+                } else if self.app.is_state(AppState::Home) {
+                    let len = self.app.dashboard.recent.len();
+                    if len > 0 {
+                        self.app
+                            .set_dashboard_state(Some(if last { len - 1 } else { 0 }));
                     }
+                } else if self.app.is_state(AppState::Journal) {
+                    let len = self.app.journal.len();
+                    if len > 0 {
+                        self.app
+                            .set_journal_state(Some(if last { len - 1 } else { 0 }));
+                    }
+                } else if let Some(len) = self.app.notes().map(Vec::len) {
+                    self.app.set_note_state(if last { len - 1 } else { 0 });
+                    self.refresh_detail();
                 }
-                KeyCode::Down => self.handle_down()?,
-                KeyCode::Up => self.handle_up()?,
-                // KeyCode::Char('p') => active_menu_item = MenuItem::Pets,
-                _ => {}
-            },
-            Event::Tick => {}
+            }
+            Action::Edit => {
+                if let Some(note) = self.app.selected_note() {
+                    self.app.editing = Some(note.clone());
+                    self.app.editor_mode = true;
+                }
+            }
+            Action::Delete => self.handle_delete()?,
+            Action::Schedule => {
+                if self.app.selected_note().is_some() {
+                    self.app.buffer.clear();
+                    self.app.input_error = false;
+                    self.app.set_state(AppState::Schedule)
+                }
+            }
+            Action::NextLink => self.handle_next_link(),
+            Action::FollowLink => self.handle_follow_link()?,
+            Action::Back => self.handle_back()?,
+            Action::NextTask => self.handle_next_task(),
+            Action::ToggleTask => self.handle_toggle_task()?,
+            Action::NextAttachment => {
+                let count = self.app.attachments.len();
+                self.app.selected_attachment = match self.app.selected_attachment {
+                    _ if count == 0 => None,
+                    Some(i) if i + 1 < count => Some(i + 1),
+                    _ => Some(0),
+                };
+            }
+            Action::OpenAttachment => {
+                if let Some(id) = self.app.selected_attachment().and_then(|a| a.id) {
+                    let attachment = self.db.fetch_attachment(id)?;
+                    match attachment::open_with_system(&attachment) {
+                        Ok(path) => self.opened_attachments.push(path),
+                        Err(err) => self
+                            .app
+                            .error(format!("could not open {}: {}", attachment.name, err)),
+                    }
+                }
+            }
+            Action::SaveAttachment => {
+                if let Some(name) = self.app.selected_attachment().map(|a| a.name.clone()) {
+                    self.app.buffer = format!("./{}", name);
+                    self.app.input_error = false;
+                    self.app.set_state(AppState::SaveAttachment)
+                }
+            }
+            Action::FilterScope => {
+                self.app.scope_filter = match self.app.scope_filter {
+                    None => Some("local"),
+                    Some("local") => Some("private"),
+                    Some("private") => Some("guild"),
+                    Some("guild") => Some("channel"),
+                    _ => None,
+                };
+                self.set_notes()
+            }
+            Action::FilterOwner => self.handle_next_owner()?,
+            Action::FilterTasks => {
+                self.app.task_filter = self.app.task_filter.next();
+                self.set_tasks()
+            }
+            Action::ToggleListedTask => self.handle_toggle_listed_task()?,
+            Action::OpenTask => self.handle_open_task(),
+            Action::QuickSwitcher => {
+                let notes = self.db.list_all_notes()?;
+                let return_to = *self.app.state();
+                self.app.switcher =
+                    Some(QuickSwitcher::new(notes, return_to, &self.app.recent_notes));
+                self.app.set_state(AppState::Switcher);
+            }
+            Action::Help => {
+                self.app.help = Some(*self.app.state());
+                self.app.set_state(AppState::Help);
+            }
+            Action::OpenEditor => self.app.editor_mode = true,
+            Action::PickTemplate => {
+                let names = self.config.templates().list()?;
+                self.app.template_picker =
+                    Some(TemplatePicker::new(names, self.app.template.as_deref()));
+                self.app.set_state(AppState::Templates);
+            }
+            Action::Cancel => {
+                self.app.set_state(AppState::Home);
+                self.app.buffer.clear();
+                self.app.template = None;
+            }
+            Action::CommandLine => {
+                let return_to = *self.app.state();
+                self.app.command_line = Some(CommandLine::new(return_to));
+                self.app.set_state(AppState::Command);
+            }
+        }
+        Ok(())
+    }
+    /// Tab completes, Up/Down walk the history, Enter runs the line
+    fn handle_command_line(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let event = match event {
+            Event::Input(event) => event,
+            Event::Tick => return Ok(()),
+        };
+        let line = match self.app.command_line.as_mut() {
+            Some(line) => line,
+            None => {
+                self.app.set_state(AppState::Home);
+                return Ok(());
+            }
         };
+        match event.code {
+            KeyCode::Esc => self.close_command_line(),
+            KeyCode::Backspace if line.input.is_empty() => self.close_command_line(),
+            KeyCode::Backspace => {
+                line.input.pop();
+                line.edited();
+            }
+            KeyCode::Tab => {
+                let themes = theme::available(&self.config.themes_dir());
+                line.complete(&themes.iter().map(String::as_str).collect::<Vec<_>>());
+            }
+            KeyCode::Up => line.history_back(&self.app.command_history),
+            KeyCode::Down => line.history_forward(&self.app.command_history),
+            KeyCode::Char(c) => {
+                line.input.push(c);
+                line.edited();
+            }
+            KeyCode::Enter => {
+                let input = line.input.trim().to_string();
+                self.close_command_line();
+                if input.is_empty() {
+                    return Ok(());
+                }
+                if self.app.command_history.last() != Some(&input) {
+                    self.app.command_history.push(input.clone());
+                }
+                match Command::parse(&input) {
+                    Ok(command) => self.run_command(command)?,
+                    Err(usage) => self.app.warn(usage),
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+    fn close_command_line(&mut self) {
+        let return_to = self
+            .app
+            .command_line
+            .take()
+            .map_or(AppState::Home, |line| line.return_to);
+        self.app.set_state(return_to);
+    }
+    fn run_command(&mut self, command: Command) -> Result<(), Box<dyn std::error::Error>> {
+        match command {
+            Command::Run(action) => self.run_action(action)?,
+            Command::New(title) => {
+                self.app.buffer = title;
+                self.app.set_state(AppState::Add);
+                self.app.editor_mode = true;
+            }
+            Command::Tag { add, tag } => {
+                let note_id = self
+                    .app
+                    .selected_note()
+                    .and_then(|note| note.id)
+                    .ok_or("select a note on the Notes tab first")?;
+                if add {
+                    self.db.tag_note(note_id, &tag)?;
+                } else {
+                    self.db.untag_note(note_id, &tag)?;
+                }
+                self.set_notes();
+                self.select_note_by_id(Some(note_id));
+            }
+            Command::Sort(sort) => {
+                self.app.sort = sort;
+                self.set_notes();
+                self.app
+                    .success(format!("notes sorted by {:?}", sort).to_lowercase());
+            }
+            Command::Export { format, path } => {
+                let count = match format {
+                    ExportFormat::Markdown => {
+                        sync::export_markdown(&self.db.list_all_notes()?, &path)?
+                    }
+                    ExportFormat::Json => {
+                        let exported = self.db.export_notes()?;
+                        std::fs::write(&path, serde_json::to_string_pretty(&exported)?)?;
+                        exported.len()
+                    }
+                };
+                self.app
+                    .success(format!("exported {} notes to {}", count, path.display()));
+            }
+            Command::EditTemplate(name) => {
+                // fail on a bad name now rather than after the editor closed
+                match self.config.templates().get(&name) {
+                    Ok(_) | Err(NoteError::TemplateNotFound(_)) => (),
+                    Err(err) => return Err(err.into()),
+                }
+                self.app.editing_template = Some(name);
+                self.app.editor_mode = true;
+            }
+            Command::RemoveTemplate(name) => {
+                self.config.templates().remove(&name)?;
+                self.app.success(format!("removed template `{}`", name));
+            }
+            Command::Theme(name) => {
+                self.widget.theme = Theme::load(&name, &self.config.themes_dir())?;
+                self.app.success(format!("switched to the {} theme", name));
+            }
+        }
         Ok(())
     }
     fn handle_delete(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let note_id = self.app.note_state().selected().ok_or("no selected note")?;
-        let note = &self.app.notes.as_ref().expect("this should not happen!")[note_id];
-        self.db.delete(note.id.ok_or("this should not happened")?)?;
-        // self.app.set_note_state(note_id);
+        let note_id = match self.app.selected_note() {
+            Some(note) => note.id.ok_or("this should not happened")?,
+            None => return Ok(()),
+        };
+        let deleted = self.db.delete_note(note_id)?;
         self.set_notes();
+        self.app.success(format!("deleted `{}`", deleted.title));
         Ok(())
     }
-    fn handle_adding(&mut self, event: Event<KeyEvent>) -> Result<(), Box<dyn std::error::Error>> {
-        match event {
-            Event::Input(event) => match event.code {
-                KeyCode::Char(c) => {
-                    if event.modifiers.contains(KeyModifiers::CONTROL) {
-                        // self.app.buffer.clear();
-                        self.app.editor_mode = true;
-                    } else {
-                        self.app.buffer.push(c);
+    /// cycles the owner filter through every owner in the database, then off
+    fn handle_next_owner(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut owners: Vec<String> = self
+            .db
+            .list_all_notes()?
+            .into_iter()
+            .filter_map(|note| note.owner)
+            .collect();
+        owners.sort();
+        owners.dedup();
+        self.app.owner_filter = match &self.app.owner_filter {
+            None => owners.first().cloned(),
+            Some(current) => owners.iter().find(|owner| *owner > current).cloned(),
+        };
+        self.set_notes();
+        Ok(())
+    }
+    fn handle_next_task(&mut self) {
+        let lines: Vec<_> = match self.app.selected_note() {
+            Some(note) => tasks::parse_tasks(note).iter().map(|t| t.line).collect(),
+            None => return,
+        };
+        self.app.selected_task_line = match self.app.selected_task_line {
+            Some(current) => lines
+                .iter()
+                .copied()
+                .find(|line| *line > current)
+                .or_else(|| lines.first().copied()),
+            None => lines.first().copied(),
+        };
+    }
+    fn handle_toggle_task(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (note_id, line) = match (self.app.selected_note(), self.app.selected_task_line) {
+            (Some(note), Some(line)) => (note.id.ok_or("this should not happened")?, line),
+            _ => return Ok(()),
+        };
+        let updated = self.db.toggle_task(note_id, line)?;
+        if let Some(note) = self
+            .app
+            .notes_mut()
+            .and_then(|notes| notes.iter_mut().find(|note| note.id == Some(note_id)))
+        {
+            *note = updated;
+        }
+        Ok(())
+    }
+    fn handle_toggle_listed_task(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(task) = self.app.selected_task() {
+            self.db.toggle_task(task.note_id, task.line)?;
+            self.set_tasks();
+        }
+        Ok(())
+    }
+    fn handle_open_task(&mut self) {
+        if let Some(note_id) = self.app.selected_task().map(|task| task.note_id) {
+            self.app.set_state(AppState::Note);
+            self.set_notes();
+            self.select_note_by_id(Some(note_id));
+        }
+    }
+    fn handle_task_move(&mut self, step: isize) {
+        let len = self.app.tasks.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let selected = self.app.task_state().selected().unwrap_or_default() as isize;
+        self.app
+            .set_task_state(Some((selected + step).rem_euclid(len) as usize));
+    }
+    fn handle_recent_move(&mut self, step: isize) {
+        let len = self.app.dashboard.recent.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let selected = self.app.dashboard_state().selected().unwrap_or_default() as isize;
+        self.app
+            .set_dashboard_state(Some((selected + step).rem_euclid(len) as usize));
+    }
+    fn handle_day_move(&mut self, step: isize) {
+        let len = self.app.journal.len() as isize;
+        if len == 0 {
+            return;
+        }
+        let selected = self.app.journal_state().selected().unwrap_or_default() as isize;
+        self.app
+            .set_journal_state(Some((selected + step).rem_euclid(len) as usize));
+    }
+    /// shows the note for `date` on the Notes tab, starting it from the journal template
+    fn open_day(&mut self, date: chrono::NaiveDate) -> Result<(), Box<dyn std::error::Error>> {
+        let template = self.config.journal_template()?;
+        let daily = self.db.open_daily_note(date, template.as_deref())?;
+        if let Some(note_id) = daily.note.id {
+            self.jump_to_note(note_id);
+        }
+        if daily.created {
+            self.app
+                .success(format!("started the daily note for {}", daily.note.title));
+        }
+        Ok(())
+    }
+    /// the nearest daily note before (`-1`) or after (`1`) the one shown, or today
+    fn step_day(&mut self, step: isize) -> Result<(), Box<dyn std::error::Error>> {
+        let shown = match self.app.state() {
+            AppState::Journal => self.app.selected_day(),
+            AppState::Note => self.app.selected_note(),
+            _ => None,
+        }
+        .and_then(journal::date_of)
+        .unwrap_or_else(journal::today);
+        let days = self.db.journal()?;
+        match journal::adjacent(&days, shown, step).and_then(|note| note.id) {
+            Some(note_id) if self.app.is_state(AppState::Journal) => {
+                self.app.journal = days.clone();
+                let index = days.iter().position(|note| note.id == Some(note_id));
+                self.app.set_journal_state(index);
+            }
+            Some(note_id) => self.jump_to_note(note_id),
+            None if step < 0 => self.app.warn("no earlier daily note"),
+            None => self.app.warn("no later daily note, t starts today's"),
+        }
+        Ok(())
+    }
+    fn handle_next_link(&mut self) {
+        let num_links = match self.app.selected_note() {
+            Some(note) => links::parse_links(&note.text).len(),
+            None => return,
+        };
+        self.app.selected_link = match self.app.selected_link {
+            _ if num_links == 0 => None,
+            Some(i) if i + 1 < num_links => Some(i + 1),
+            _ => Some(0),
+        };
+    }
+    fn handle_follow_link(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let (current_id, target) = match (self.app.selected_note(), self.app.selected_link) {
+            (Some(note), Some(i)) => match links::parse_links(&note.text).get(i) {
+                Some(target) => (note.id, target.clone()),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        if let Some(target) = self.db.resolve_link(&target)? {
+            if self.select_note_by_id(target.id) {
+                if let Some(id) = current_id {
+                    self.app.link_history.push(id);
+                }
+            }
+        }
+        Ok(())
+    }
+    fn handle_back(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some(id) = self.app.link_history.pop() {
+            if self.select_note_by_id(Some(id)) {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// moves the list selection onto the note with this id, if it is loaded
+    fn select_note_by_id(&mut self, note_id: Option<usize>) -> bool {
+        let index = self
+            .app
+            .notes()
+            .and_then(|notes| notes.iter().position(|note| note.id == note_id));
+        match index {
+            Some(index) => {
+                self.app.set_note_state(index);
+                if let Some(note_id) = note_id {
+                    self.app.touch_recent(note_id);
+                }
+                self.refresh_detail();
+                true
+            }
+            None => false,
+        }
+    }
+    /// reads a `+30m` / `YYYY-MM-DD HH:MM` due time for the selected note
+    fn handle_scheduling(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Event::Input(event) = event {
+            match event.code {
+                KeyCode::Char(c) => self.app.buffer.push(c),
+                KeyCode::Backspace => {
+                    self.app.buffer.pop();
+                }
+                KeyCode::Enter => {
+                    let note_id = self
+                        .app
+                        .selected_note()
+                        .and_then(|note| note.id)
+                        .ok_or("no selected note")?;
+                    match schedule::parse_when(&self.app.buffer, chrono::Utc::now()) {
+                        Some(due_at) => {
+                            self.db.set_due(note_id, Some(due_at))?;
+                            self.db.set_reminder(note_id, Some(due_at))?;
+                            self.app.buffer.clear();
+                            self.app.set_state(AppState::Note);
+                            self.set_notes();
+                            self.select_note_by_id(Some(note_id));
+                            self.app
+                                .success(format!("due {}", schedule::format_when(due_at)));
+                        }
+                        None => self.app.input_error = true,
                     }
                 }
+                KeyCode::Esc => {
+                    self.app.set_state(AppState::Note);
+                    self.app.buffer.clear()
+                }
+                _ => (),
+            }
+        };
+        Ok(())
+    }
+    fn handle_save_attachment(
+        &mut self,
+        event: Event<KeyEvent>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Event::Input(event) = event {
+            match event.code {
+                KeyCode::Char(c) => self.app.buffer.push(c),
                 KeyCode::Backspace => {
                     self.app.buffer.pop();
                 }
-                // KeyCode::Enter => {
-                //     self.app.buffer.push_str("\n");
-                // }
+                KeyCode::Enter => {
+                    let attachment_id = self
+                        .app
+                        .selected_attachment()
+                        .and_then(|a| a.id)
+                        .ok_or("no selected attachment")?;
+                    let path = std::path::PathBuf::from(&self.app.buffer);
+                    match self.db.extract_attachment(attachment_id, &path) {
+                        Ok(()) => {
+                            self.app.buffer.clear();
+                            self.app.set_state(AppState::Note);
+                            self.app.success(format!("saved {}", path.display()));
+                        }
+                        Err(NoteError::Io(_)) => self.app.input_error = true,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
                 KeyCode::Esc => {
-                    self.app.set_state(AppState::Home);
+                    self.app.set_state(AppState::Note);
                     self.app.buffer.clear()
                 }
                 _ => (),
-            },
-            _ => (),
+            }
         };
         Ok(())
     }
-    fn handle_up(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        {
-            if let Some(selected) = self.app.note_state().selected() {
-                let num_notes = self
-                    .app
-                    .notes
-                    .as_ref()
-                    .ok_or("this should not happen!")?
-                    .len();
-                if num_notes == 0 {
-                    self.app.set_note_state(0);
-                    return Ok(());
+    fn handle_unlock(&mut self, event: Event<KeyEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Event::Input(event) = event {
+            let control = event.modifiers.contains(KeyModifiers::CONTROL);
+            match event.code {
+                KeyCode::Char('c') if control => self.app.should_exist = true,
+                KeyCode::Char(c) if !control => self.app.buffer.push(c),
+                KeyCode::Backspace => {
+                    self.app.buffer.pop();
                 }
-                if selected > 0 {
-                    self.app.set_note_state(selected - 1);
-                } else {
-                    self.app.set_note_state(num_notes - 1);
+                KeyCode::Enter => {
+                    match self.db.unlock(&self.app.buffer) {
+                        Ok(()) => self.app.set_state(AppState::Home),
+                        Err(NoteError::WrongPassphrase) => self.app.input_error = true,
+                        Err(err) => return Err(err.into()),
+                    }
+                    self.app.buffer.clear();
                 }
+                KeyCode::Esc => self.app.should_exist = true,
+                _ => (),
             }
-        }
+        };
         Ok(())
     }
-    fn handle_down(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(selected) = self.app.note_state().selected() {
-            let num_notes = self
-                .app
-                .notes
-                .as_ref()
-                .ok_or("this should not happen!")?
-                .len();
-            if num_notes == 0 {
-                self.app.set_note_state(0);
-                return Ok(());
+    fn handle_adding(&mut self, event: Event<KeyEvent>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Event::Input(event) = event {
+            let key = Key::from_event(&event);
+            if let Lookup::Run(action) = self.keymap.lookup(AppState::Add, &[key]) {
+                return self.run_action(action);
             }
-            if selected >= num_notes - 1 {
-                self.app.set_note_state(0);
-            } else {
-                self.app.set_note_state(selected + 1);
+            match event.code {
+                KeyCode::Char(c) if !event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.app.buffer.push(c)
+                }
+                KeyCode::Backspace => {
+                    self.app.buffer.pop();
+                }
+                _ => (),
             }
-        }
+        };
         Ok(())
     }
+    /// moves the notes list selection, wrapping around at either end
+    fn handle_note_move(&mut self, step: isize) {
+        let len = self.app.notes().map_or(0, Vec::len) as isize;
+        if len == 0 {
+            return;
+        }
+        let selected = self.app.note_state().selected().unwrap_or_default() as isize;
+        self.app
+            .set_note_state((selected + step).rem_euclid(len) as usize);
+    }
 
     pub fn render_main_frame(
         &self,
@@ -172,39 +1069,242 @@ impl AppHandler {
         Render::render(
             frame,
             self.widget.header,
-            self.widget.render_tabs(self.app.state().clone()),
+            self.widget.render_tabs(self.app.tab_state()),
+        );
+        let db_path = self.db.db_path().map_or_else(
+            || "in memory".to_string(),
+            |path| path.display().to_string(),
+        );
+        let pending = (!self.pending_keys.is_empty())
+            .then(|| format!("{} ...", sequence_label(&self.pending_keys)));
+        Render::render(
+            frame,
+            self.widget.footer,
+            self.widget.render_status_bar(
+                self.app.tab_state().name(),
+                self.app.note_count,
+                db_path,
+                pending,
+            ),
+        );
+        if let Some(toast) = &self.app.toast {
+            Render::render(
+                frame,
+                self.widget.toast_area(&toast.text),
+                self.widget.render_toast(toast),
+            );
+        }
+        Ok(())
+    }
+    fn render_note_view(
+        &self,
+        frame: &mut Frame<CrosstermBackend<Stdout>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.render_main_frame(frame)?;
+        let notes = match self.app.notes() {
+            Some(s) => s,
+            None => {
+                render::Render::render(
+                    frame,
+                    self.widget.main_part,
+                    self.widget
+                        .render_empty_notes(self.app.is_filtered(), self.app.filter_label()),
+                );
+                return Ok(());
+            }
+        };
+        let note_area = self.widget.notes_area();
+        let detail_area = self.widget.note_detail_area();
+        let (left, right) = self.widget.render_notes(
+            self.app.note_state(),
+            notes,
+            self.app.selected_task_line,
+            self.app.filter_label(),
+        );
+        render::Render::render(frame, detail_area[0], right);
+        if let Some(note) = self.app.selected_note() {
+            render::Render::render(
+                frame,
+                detail_area[1],
+                self.widget.render_links(
+                    &links::parse_links(&note.text),
+                    self.app.selected_link,
+                    &self.app.backlinks,
+                    &self.app.attachments,
+                    self.app.selected_attachment,
+                ),
+            );
+        }
+        render::Render::render_stateful(
+            frame,
+            note_area[0],
+            left,
+            &mut self.app.note_state().clone(),
         );
-        Render::render(frame, self.widget.footer, self.widget.render_copyright());
         Ok(())
     }
     pub fn render(
         &self,
         frame: &mut Frame<CrosstermBackend<Stdout>>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        match self.app.state() {
+        self.render_state(frame, *self.app.state())
+    }
+    fn render_state(
+        &self,
+        frame: &mut Frame<CrosstermBackend<Stdout>>,
+        state: AppState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match state {
             AppState::Home => {
                 self.render_main_frame(frame)?;
-                render::Render::render(frame, self.widget.home_area(), self.widget.render_home())
+                let area = self.widget.home_area();
+                let dashboard = &self.app.dashboard;
+                let bars = Widget::week_bars(dashboard);
+                render::Render::render(frame, area[0], self.widget.render_home(dashboard));
+                render::Render::render(frame, area[1], self.widget.render_week_chart(&bars));
+                render::Render::render_stateful(
+                    frame,
+                    area[2],
+                    self.widget.render_recent(&dashboard.recent),
+                    &mut self.app.dashboard_state().clone(),
+                );
+                render::Render::render(
+                    frame,
+                    area[3],
+                    self.widget.render_upcoming(&dashboard.upcoming),
+                );
             }
-            AppState::Note => {
-                self.render_main_frame(frame)?;
-                let notes = match self.app.notes.as_ref() {
-                    Some(s) => s,
-                    None => return Ok(()),
+            AppState::Note => self.render_note_view(frame)?,
+            AppState::Schedule => {
+                self.render_note_view(frame)?;
+                let title = if self.app.input_error {
+                    "invalid time, use +30m or YYYY-MM-DD HH:MM"
+                } else {
+                    "due (+30m or YYYY-MM-DD HH:MM)"
                 };
-                let note_area = self.widget.notes_area();
-                let (left, right) = self.widget.render_notes(self.app.note_state(), notes);
-                render::Render::render(frame, note_area[1], right);
+                render::Render::render(
+                    frame,
+                    self.widget.add_note_area(),
+                    self.widget.render_prompt(&self.app.buffer, title),
+                );
+            }
+            AppState::SaveAttachment => {
+                self.render_note_view(frame)?;
+                let title = if self.app.input_error {
+                    "could not write there, try another path"
+                } else {
+                    "save attachment to"
+                };
+                render::Render::render(
+                    frame,
+                    self.widget.add_note_area(),
+                    self.widget.render_prompt(&self.app.buffer, title),
+                );
+            }
+            AppState::Unlock => {
+                let masked = "*".repeat(self.app.buffer.chars().count());
+                let title = if self.app.input_error {
+                    "wrong passphrase, try again (Esc quits)"
+                } else {
+                    "passphrase (Esc quits)"
+                };
+                render::Render::render(
+                    frame,
+                    self.widget.add_note_area(),
+                    self.widget.render_prompt(&masked, title),
+                );
+            }
+            AppState::Tasks => {
+                self.render_main_frame(frame)?;
                 render::Render::render_stateful(
                     frame,
-                    note_area[0],
-                    left,
-                    &mut self.app.note_state().clone(),
+                    self.widget.main_part,
+                    self.widget
+                        .render_tasks(&self.app.tasks, self.app.task_filter),
+                    &mut self.app.task_state().clone(),
+                );
+            }
+            AppState::Conflict => {
+                self.render_main_frame(frame)?;
+                if let Some(conflict) = &self.app.conflict {
+                    let area = self.widget.conflict_area();
+                    let (mine, theirs) = self
+                        .widget
+                        .render_conflict(&conflict.mine, &conflict.theirs);
+                    render::Render::render(frame, area[0], mine);
+                    render::Render::render(frame, area[1], theirs);
+                }
+            }
+            AppState::Command => {
+                if let Some(line) = &self.app.command_line {
+                    self.render_state(frame, line.return_to)?;
+                    render::Render::render(frame, self.widget.footer, Clear);
+                    render::Render::render(
+                        frame,
+                        self.widget.footer,
+                        self.widget.render_command_line(line),
+                    );
+                }
+            }
+            AppState::Help => {
+                let state = self.app.help.unwrap_or(AppState::Home);
+                self.render_state(frame, state)?;
+                let area = self.widget.popup_area();
+                render::Render::render(frame, area, Clear);
+                render::Render::render(
+                    frame,
+                    area,
+                    self.widget
+                        .render_help(&self.keymap.help(state), Mode::of(state)),
+                );
+            }
+            AppState::Switcher => {
+                if let Some(switcher) = &self.app.switcher {
+                    self.render_state(frame, switcher.return_to)?;
+                    let area = self.widget.switcher_area();
+                    let (prompt, matches) = self.widget.render_switcher(switcher);
+                    render::Render::render(frame, area[0], Clear);
+                    render::Render::render(frame, area[1], Clear);
+                    render::Render::render(frame, area[0], prompt);
+                    render::Render::render_stateful(
+                        frame,
+                        area[1],
+                        matches,
+                        &mut switcher.state.clone(),
+                    );
+                }
+            }
+            AppState::Journal => {
+                self.render_main_frame(frame)?;
+                render::Render::render_stateful(
+                    frame,
+                    self.widget.main_part,
+                    self.widget.render_journal(&self.app.journal),
+                    &mut self.app.journal_state().clone(),
                 );
             }
             AppState::Add => {
                 let area = self.widget.add_note_area();
-                render::Render::render(frame, area, self.widget.render_add_note(&self.app.buffer));
+                render::Render::render(
+                    frame,
+                    area,
+                    self.widget
+                        .render_add_note(&self.app.buffer, self.app.template.as_deref()),
+                );
+            }
+            AppState::Templates => {
+                self.render_state(frame, AppState::Add)?;
+                if let Some(picker) = &self.app.template_picker {
+                    let area = self.widget.popup_area();
+                    render::Render::render(frame, area, Clear);
+                    render::Render::render_stateful(
+                        frame,
+                        area,
+                        self.widget
+                            .render_template_picker(&picker.names, self.config.templates().path()),
+                        &mut picker.state.clone(),
+                    );
+                }
             }
             _ => (),
         }
@@ -0,0 +1,101 @@
+use chrono::{Local, NaiveDate, TimeZone};
+
+use crate::{template, Note};
+
+/// daily notes are titled with their date in this format
+const TITLE_FORMAT: &str = "%Y-%m-%d";
+
+/// the journal entry for a day, and whether opening it created it
+#[derive(Clone, Debug)]
+pub struct DailyNote {
+    pub note: Note,
+    pub created: bool,
+    /// where `{{cursor}}` stood in the template of a created note
+    pub cursor: Option<(usize, usize)>,
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+pub fn title_of(date: NaiveDate) -> String {
+    date.format(TITLE_FORMAT).to_string()
+}
+
+/// the day a daily note is for, `None` for every other note
+pub fn date_of(note: &Note) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(note.title.trim(), TITLE_FORMAT).ok()
+}
+
+/// the nearest daily note before `shown` when `step` is negative, after it otherwise,
+/// skipping days without one, from `days` in any order
+pub fn adjacent(days: &[Note], shown: NaiveDate, step: isize) -> Option<&Note> {
+    let dated = days.iter().filter_map(|note| Some((date_of(note)?, note)));
+    if step < 0 {
+        dated
+            .filter(|(date, _)| *date < shown)
+            .max_by_key(|(date, _)| *date)
+    } else {
+        dated
+            .filter(|(date, _)| *date > shown)
+            .min_by_key(|(date, _)| *date)
+    }
+    .map(|(_, note)| note)
+}
+
+/// the template expanded for `date`, `{{date}}` being that day rather than today
+pub fn daily_text(date: NaiveDate, template: Option<&str>) -> template::Expanded {
+    let now = Local::now();
+    let at = Local
+        .from_local_datetime(&date.and_time(now.time()))
+        .earliest()
+        .unwrap_or(now);
+    template::expand_template(template.unwrap_or_default(), &title_of(date), at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    fn note(id: usize, title: &str) -> Note {
+        Note::new(Some(id), title.into(), String::new())
+    }
+
+    #[test]
+    fn only_notes_titled_with_a_date_are_daily_notes() {
+        assert_eq!(date_of(&note(1, " 2024-03-09 ")), Some(day(9)));
+        for title in ["2024-3-9x", "09.03.2024", "2024-02-30", "journal"] {
+            assert_eq!(date_of(&note(1, title)), None, "{title:?}");
+        }
+        assert_eq!(title_of(day(9)), "2024-03-09");
+    }
+
+    #[test]
+    fn steps_to_the_nearest_day_with_a_note() {
+        // unsorted, with gaps and an undated note in between
+        let days = [
+            note(1, "2024-03-01"),
+            note(2, "2024-03-20"),
+            note(3, "ideas"),
+            note(4, "2024-03-05"),
+        ];
+        let id = |found: Option<&Note>| found.and_then(|note| note.id);
+        assert_eq!(id(adjacent(&days, day(5), -1)), Some(1));
+        assert_eq!(id(adjacent(&days, day(5), 1)), Some(2));
+        assert_eq!(id(adjacent(&days, day(12), -1)), Some(4));
+        assert_eq!(id(adjacent(&days, day(12), 1)), Some(2));
+        assert_eq!(id(adjacent(&days, day(1), -1)), None);
+        assert_eq!(id(adjacent(&days, day(20), 1)), None);
+    }
+
+    #[test]
+    fn the_daily_template_dates_the_day_not_today() {
+        let expanded = daily_text(day(9), Some("{{title}} {{date}}"));
+        assert_eq!(expanded.text, "2024-03-09 2024-03-09");
+        assert_eq!(daily_text(day(9), None).text, "");
+    }
+}
@@ -0,0 +1,584 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppState, command::Action};
+
+/// the set of bindings in effect, picked by the tab being shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// every tab, unless the tab binds the key itself
+    Global,
+    Home,
+    Notes,
+    Tasks,
+    Journal,
+    /// the title prompt of a new note, where other keys are typed
+    Add,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 6] = [
+        Mode::Global,
+        Mode::Home,
+        Mode::Notes,
+        Mode::Tasks,
+        Mode::Journal,
+        Mode::Add,
+    ];
+
+    pub fn of(state: AppState) -> Mode {
+        match state {
+            AppState::Home => Mode::Home,
+            AppState::Note => Mode::Notes,
+            AppState::Tasks => Mode::Tasks,
+            AppState::Journal => Mode::Journal,
+            AppState::Add => Mode::Add,
+            _ => Mode::Global,
+        }
+    }
+    /// whether global bindings apply when the mode does not bind a key itself
+    pub fn inherits_global(self) -> bool {
+        self != Mode::Add
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Global => "global",
+            Mode::Home => "home",
+            Mode::Notes => "notes",
+            Mode::Tasks => "tasks",
+            Mode::Journal => "journal",
+            Mode::Add => "add",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+impl Action {
+    /// where the action makes sense, global ones run anywhere
+    pub fn mode(self) -> Mode {
+        match self {
+            Action::Edit
+            | Action::Delete
+            | Action::Schedule
+            | Action::NextLink
+            | Action::FollowLink
+            | Action::Back
+            | Action::NextTask
+            | Action::ToggleTask
+            | Action::NextAttachment
+            | Action::OpenAttachment
+            | Action::SaveAttachment
+            | Action::FilterScope
+            | Action::FilterOwner => Mode::Notes,
+            Action::OpenRecent => Mode::Home,
+            Action::OpenDay => Mode::Journal,
+            Action::FilterTasks | Action::ToggleListedTask | Action::OpenTask => Mode::Tasks,
+            Action::OpenEditor | Action::PickTemplate | Action::Cancel => Mode::Add,
+            _ => Mode::Global,
+        }
+    }
+}
+
+/// one key press with its modifiers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub const fn new(code: KeyCode) -> Key {
+        Key {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+    /// shift is already part of the character typed, so it is dropped for chars
+    pub fn from_event(event: &KeyEvent) -> Key {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        Key {
+            code: event.code,
+            modifiers,
+        }
+    }
+    /// a key that types a visible character
+    pub fn is_plain_char(&self) -> bool {
+        matches!(self.code, KeyCode::Char(c) if c != ' ') && self.modifiers.is_empty()
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// `q`, `Enter`, `F1`, `Ctrl-p` / `C-p`, `Alt-x` / `M-x`
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const PREFIXES: [(&str, KeyModifiers); 7] = [
+            ("ctrl-", KeyModifiers::CONTROL),
+            ("ctrl+", KeyModifiers::CONTROL),
+            ("c-", KeyModifiers::CONTROL),
+            ("alt-", KeyModifiers::ALT),
+            ("alt+", KeyModifiers::ALT),
+            ("m-", KeyModifiers::ALT),
+            ("a-", KeyModifiers::ALT),
+        ];
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        while let Some((prefix, modifier)) = PREFIXES.iter().find(|(prefix, _)| {
+            rest.len() > prefix.len() && rest.to_lowercase().starts_with(prefix)
+        }) {
+            modifiers |= *modifier;
+            rest = &rest[prefix.len()..];
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            // terminals report Ctrl with a letter as the lowercase letter
+            (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" | "return" | "ret" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" | "bs" => KeyCode::Backspace,
+                "space" | "spc" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "delete" | "del" => KeyCode::Delete,
+                name => match name.strip_prefix('f').map(str::parse::<u8>) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{}`", s)),
+                },
+            },
+        };
+        Ok(Key { code, modifiers })
+    }
+}
+
+/// `gg`, `C-x b` or `Ctrl-x Ctrl-s`: keys separated by spaces, runs of plain chars split up
+pub fn parse_sequence(spec: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    for word in spec.split_whitespace() {
+        match word.parse::<Key>() {
+            Ok(key) => keys.push(key),
+            Err(err) if word.contains(['-', '+']) => return Err(err),
+            Err(_) => keys.extend(word.chars().map(|c| Key::new(KeyCode::Char(c)))),
+        }
+    }
+    if keys.is_empty() {
+        return Err("empty key sequence".to_string());
+    }
+    Ok(keys)
+}
+
+/// how a key sequence is shown, `gg` for plain chars and `Ctrl-x b` otherwise
+pub fn sequence_label(keys: &[Key]) -> String {
+    if keys.len() > 1 && keys.iter().all(Key::is_plain_char) {
+        return keys.iter().map(Key::to_string).collect();
+    }
+    keys.iter()
+        .map(Key::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub mode: Mode,
+    pub keys: Vec<Key>,
+    pub action: Action,
+}
+
+/// the bindings every preset starts from
+const DEFAULT_BINDINGS: &[(Mode, &str, Action)] = &[
+    (Mode::Global, "q", Action::Quit),
+    (Mode::Global, "h", Action::Home),
+    (Mode::Global, "n", Action::Notes),
+    (Mode::Global, "a", Action::New),
+    (Mode::Global, "T", Action::Tasks),
+    (Mode::Global, "J", Action::Journal),
+    (Mode::Global, "t", Action::Today),
+    (Mode::Global, "[", Action::PreviousDay),
+    (Mode::Global, "]", Action::NextDay),
+    (Mode::Global, "Up", Action::Up),
+    (Mode::Global, "Down", Action::Down),
+    (Mode::Global, "Home", Action::Top),
+    (Mode::Global, "End", Action::Bottom),
+    (Mode::Global, "C-p", Action::QuickSwitcher),
+    (Mode::Global, ":", Action::CommandLine),
+    (Mode::Global, "?", Action::Help),
+    (Mode::Global, "F1", Action::Help),
+    (Mode::Home, "Enter", Action::OpenRecent),
+    (Mode::Notes, "e", Action::Edit),
+    (Mode::Notes, "d", Action::Delete),
+    (Mode::Notes, "r", Action::Schedule),
+    (Mode::Notes, "Tab", Action::NextLink),
+    (Mode::Notes, "Enter", Action::FollowLink),
+    (Mode::Notes, "Backspace", Action::Back),
+    (Mode::Notes, "c", Action::NextTask),
+    (Mode::Notes, "Space", Action::ToggleTask),
+    (Mode::Notes, "v", Action::NextAttachment),
+    (Mode::Notes, "o", Action::OpenAttachment),
+    (Mode::Notes, "s", Action::SaveAttachment),
+    (Mode::Notes, "F", Action::FilterScope),
+    (Mode::Notes, "O", Action::FilterOwner),
+    (Mode::Tasks, "f", Action::FilterTasks),
+    (Mode::Tasks, "Space", Action::ToggleListedTask),
+    (Mode::Tasks, "Enter", Action::OpenTask),
+    (Mode::Journal, "Enter", Action::OpenDay),
+    (Mode::Add, "C-e", Action::OpenEditor),
+    (Mode::Add, "C-t", Action::PickTemplate),
+    (Mode::Add, "Esc", Action::Cancel),
+    (Mode::Add, "F1", Action::Help),
+];
+
+/// changes the vim preset makes to the default bindings, `None` unbinds
+const VIM_OVERRIDES: &[(Mode, &str, Option<Action>)] = &[
+    (Mode::Global, "j", Some(Action::Down)),
+    (Mode::Global, "k", Some(Action::Up)),
+    (Mode::Global, "gg", Some(Action::Top)),
+    (Mode::Global, "G", Some(Action::Bottom)),
+    (Mode::Global, "/", Some(Action::QuickSwitcher)),
+    (Mode::Notes, "d", None),
+    (Mode::Notes, "dd", Some(Action::Delete)),
+    (Mode::Notes, "i", Some(Action::Edit)),
+    (Mode::Notes, "C-o", Some(Action::Back)),
+    (Mode::Tasks, "x", Some(Action::ToggleListedTask)),
+];
+
+/// changes the emacs preset makes to the default bindings, `None` unbinds
+const EMACS_OVERRIDES: &[(Mode, &str, Option<Action>)] = &[
+    (Mode::Global, "C-n", Some(Action::Down)),
+    (Mode::Global, "C-p", Some(Action::Up)),
+    (Mode::Global, "M-<", Some(Action::Top)),
+    (Mode::Global, "M->", Some(Action::Bottom)),
+    (Mode::Global, "C-x b", Some(Action::QuickSwitcher)),
+    (Mode::Global, "C-x C-c", Some(Action::Quit)),
+    (Mode::Global, "M-x", Some(Action::CommandLine)),
+    (Mode::Notes, "C-x k", Some(Action::Delete)),
+    (Mode::Notes, "C-x C-q", Some(Action::Edit)),
+    (Mode::Add, "C-g", Some(Action::Cancel)),
+];
+
+/// a shipped set of bindings user overrides are applied on top of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+/// what the user changes in the keymap, the `keymap` section of the config
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub preset: Preset,
+    /// mode name to key sequence to action name, or `none` to unbind the keys
+    pub bindings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// what the handler does with the keys typed so far
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lookup {
+    Run(Action),
+    /// the keys start a longer sequence, wait for the next one
+    Pending,
+    Unbound,
+}
+
+/// the key bindings the handler dispatches on
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    /// the preset with the user's bindings applied, rejecting bindings that can't all work
+    pub fn from_config(config: &KeymapConfig) -> Result<Keymap, String> {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for (mode, spec, action) in DEFAULT_BINDINGS {
+            keymap.set(*mode, parse_sequence(spec)?, Some(*action));
+        }
+        let overrides = match config.preset {
+            Preset::Default => &[][..],
+            Preset::Vim => VIM_OVERRIDES,
+            Preset::Emacs => EMACS_OVERRIDES,
+        };
+        for (mode, spec, action) in overrides {
+            keymap.set(*mode, parse_sequence(spec)?, *action);
+        }
+        for (mode_name, bindings) in &config.bindings {
+            let mode = Mode::from_name(mode_name).ok_or_else(|| {
+                let names: Vec<&str> = Mode::ALL.iter().map(|mode| mode.name()).collect();
+                format!(
+                    "unknown keymap mode `{}`, expected one of {}",
+                    mode_name,
+                    names.join(", ")
+                )
+            })?;
+            let mut seen: Vec<(Vec<Key>, &str)> = Vec::new();
+            for (spec, name) in bindings {
+                let keys = parse_sequence(spec).map_err(|err| format!("{}: {}", mode_name, err))?;
+                if let Some((_, first)) = seen.iter().find(|(known, _)| *known == keys) {
+                    return Err(format!(
+                        "{}: `{}` and `{}` are the same keys",
+                        mode_name, first, spec
+                    ));
+                }
+                seen.push((keys.clone(), spec));
+                let action = match name.as_str() {
+                    "none" => None,
+                    name => Some(
+                        Action::from_name(name)
+                            .ok_or_else(|| format!("{}: unknown action `{}`", mode_name, name))?,
+                    ),
+                };
+                keymap.set(mode, keys, action);
+            }
+        }
+        keymap.check()?;
+        Ok(keymap)
+    }
+    /// binds the keys in the mode, replacing what they were bound to
+    fn set(&mut self, mode: Mode, keys: Vec<Key>, action: Option<Action>) {
+        self.bindings
+            .retain(|binding| binding.mode != mode || binding.keys != keys);
+        if let Some(action) = action {
+            self.bindings.push(Binding { mode, keys, action });
+        }
+    }
+    /// finds bindings that could never run
+    fn check(&self) -> Result<(), String> {
+        for binding in &self.bindings {
+            let label = sequence_label(&binding.keys);
+            let mode = binding.action.mode();
+            if mode != Mode::Global && mode != binding.mode {
+                return Err(format!(
+                    "`{}` binds {} in {} mode but it only works in {} mode",
+                    label,
+                    binding.action.name(),
+                    binding.mode.name(),
+                    mode.name()
+                ));
+            }
+            if binding.mode == Mode::Add
+                && (binding.keys.len() > 1 || binding.keys[0].is_plain_char())
+            {
+                return Err(format!(
+                    "`{}` can't be bound in add mode, only single keys that type nothing can",
+                    label
+                ));
+            }
+            let longer = self.bindings.iter().find(|other| {
+                other.mode == binding.mode
+                    && other.keys.len() > binding.keys.len()
+                    && other.keys.starts_with(&binding.keys)
+            });
+            if let Some(longer) = longer {
+                return Err(format!(
+                    "{} mode: `{}` ({}) hides `{}` ({}), unbind one of them",
+                    binding.mode.name(),
+                    label,
+                    binding.action.name(),
+                    sequence_label(&longer.keys),
+                    longer.action.name()
+                ));
+            }
+            // the same keys override a global binding on purpose, but a prefix either way
+            // leaves the global sequence unreachable on that tab
+            let clash = self.bindings.iter().find(|global| {
+                global.mode == Mode::Global
+                    && binding.mode != Mode::Global
+                    && binding.mode.inherits_global()
+                    && global.keys != binding.keys
+                    && (global.keys.starts_with(&binding.keys)
+                        || binding.keys.starts_with(&global.keys))
+            });
+            if let Some(global) = clash {
+                return Err(format!(
+                    "{} mode: `{}` ({}) hides the global `{}` ({}) there, unbind one of them",
+                    binding.mode.name(),
+                    label,
+                    binding.action.name(),
+                    sequence_label(&global.keys),
+                    global.action.name()
+                ));
+            }
+        }
+        Ok(())
+    }
+    /// what the keys typed so far do on this tab, its own bindings winning over global ones
+    pub fn lookup(&self, state: AppState, keys: &[Key]) -> Lookup {
+        let find = |mode: Mode| {
+            let mut bindings = self.bindings.iter().filter(|binding| binding.mode == mode);
+            if let Some(binding) = bindings.clone().find(|binding| binding.keys == keys) {
+                Lookup::Run(binding.action)
+            } else if bindings.any(|binding| binding.keys.starts_with(keys)) {
+                Lookup::Pending
+            } else {
+                Lookup::Unbound
+            }
+        };
+        let mode = Mode::of(state);
+        match find(mode) {
+            Lookup::Unbound if mode.inherits_global() => find(Mode::Global),
+            found => found,
+        }
+    }
+    /// every action reachable from this state with the key sequences that trigger it
+    pub fn help(&self, state: AppState) -> Vec<(Vec<Vec<Key>>, Action)> {
+        let mode = Mode::of(state);
+        let mut entries: Vec<(Vec<Vec<Key>>, Action)> = Vec::new();
+        let bindings = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.mode == mode)
+            .chain(
+                self.bindings
+                    .iter()
+                    .filter(|binding| mode != Mode::Global && binding.mode == Mode::Global),
+            );
+        for binding in bindings {
+            // only list keys that reach the binding, not ones the tab took over
+            if self.lookup(state, &binding.keys) != Lookup::Run(binding.action) {
+                continue;
+            }
+            match entries
+                .iter_mut()
+                .find(|(_, action)| *action == binding.action)
+            {
+                Some((keys, _)) => keys.push(binding.keys.clone()),
+                None => entries.push((vec![binding.keys.clone()], binding.action)),
+            }
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(preset: Preset, bindings: &[(&str, &str, &str)]) -> Result<Keymap, String> {
+        let mut config = KeymapConfig {
+            preset,
+            bindings: BTreeMap::new(),
+        };
+        for (mode, keys, action) in bindings {
+            config
+                .bindings
+                .entry(mode.to_string())
+                .or_default()
+                .insert(keys.to_string(), action.to_string());
+        }
+        Keymap::from_config(&config)
+    }
+
+    fn rejected(preset: Preset, bindings: &[(&str, &str, &str)]) -> String {
+        match keymap(preset, bindings) {
+            Ok(_) => panic!("{bindings:?} was accepted"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn every_preset_passes_its_own_check() {
+        for preset in [Preset::Default, Preset::Vim, Preset::Emacs] {
+            assert!(keymap(preset, &[]).is_ok(), "{preset:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_sequence_hidden_by_its_prefix() {
+        assert_eq!(
+            rejected(Preset::Default, &[("notes", "ee", "delete")]),
+            "notes mode: `e` (edit) hides `ee` (delete), unbind one of them"
+        );
+        assert!(keymap(
+            Preset::Default,
+            &[("notes", "e", "none"), ("notes", "ee", "edit")]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_a_tab_binding_that_hides_a_global_one() {
+        assert_eq!(
+            rejected(Preset::Vim, &[("notes", "g", "top")]),
+            "notes mode: `g` (top) hides the global `gg` (top) there, unbind one of them"
+        );
+        assert_eq!(
+            rejected(Preset::Default, &[("tasks", "qq", "filter-tasks")]),
+            "tasks mode: `qq` (filter-tasks) hides the global `q` (quit) there, unbind one of them"
+        );
+        // the very same keys override the global binding on that tab
+        assert!(keymap(Preset::Default, &[("tasks", "q", "filter-tasks")]).is_ok());
+    }
+
+    #[test]
+    fn add_mode_only_takes_single_keys_that_type_nothing() {
+        let message = "can't be bound in add mode, only single keys that type nothing can";
+        assert_eq!(
+            rejected(Preset::Default, &[("add", "x", "cancel")]),
+            format!("`x` {}", message)
+        );
+        assert_eq!(
+            rejected(Preset::Default, &[("add", "jk", "cancel")]),
+            format!("`jk` {}", message)
+        );
+        assert!(keymap(Preset::Default, &[("add", "C-c", "cancel")]).is_ok());
+    }
+
+    #[test]
+    fn rejects_actions_in_the_wrong_mode_and_unknown_modes() {
+        assert_eq!(
+            rejected(Preset::Default, &[("tasks", "x", "delete")]),
+            "`x` binds delete in tasks mode but it only works in notes mode"
+        );
+        assert_eq!(
+            rejected(Preset::Default, &[("nope", "x", "quit")]),
+            "unknown keymap mode `nope`, expected one of global, home, notes, tasks, journal, add"
+        );
+    }
+}
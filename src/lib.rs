@@ -1,28 +1,94 @@
 use std::io::Stdout;
+mod access;
 mod app;
+mod attachment;
+mod command;
+mod config;
+mod crypto;
+mod dashboard;
 mod editor_handler;
+mod encrypted;
+mod error;
+mod export;
+mod fuzzy;
 mod handler;
+mod journal;
+mod keymap;
+mod links;
+mod memory;
 mod render;
 mod repository;
+mod schedule;
 mod service;
+mod store;
+mod sync;
+mod tags;
+mod tasks;
+mod template;
+mod theme;
 mod widgets;
-use self::{app::App, repository::Repository};
+use self::app::App;
+pub use access::{Caller, Scope};
+pub use attachment::Attachment;
+pub use config::{Config, ConfigError};
+pub use dashboard::Dashboard;
+pub use editor_handler::edit_text_with_vim_at;
+pub use encrypted::EncryptedStore;
+pub use error::{NoteError, NoteResult};
+pub use export::{ExportedAttachment, ExportedNote};
 pub use handler::AppHandler;
 pub use handler::Event;
-pub use repository::Note;
+pub use journal::DailyNote;
+pub use memory::MemoryRepository;
+pub use repository::{Note, Repository};
+pub use schedule::{format_when, parse_duration, parse_when};
 pub use service::NoteService;
+pub use store::NoteStore;
+pub use sync::{sync_dir, Conflict, Prefer, SyncReport};
+pub use tasks::{Task, TaskFilter};
+pub use template::{expand_template, Expanded, TemplateDir};
 use tui::{backend::CrosstermBackend, layout::Rect, Terminal};
 pub type CrossTerminal = Terminal<CrosstermBackend<Stdout>>;
 
 pub type ResultDynError<T> = Result<T, Box<dyn std::error::Error>>;
-pub fn get_handler(f: Rect) -> ResultDynError<AppHandler> {
-    let db = Repository::new("./notes.db")?;
-    db.initialize_db()?;
+/// the TUI handler for `./notes.db`, drawn with the keymap and theme from `config`
+pub fn get_handler(f: Rect, config: Config) -> ResultDynError<AppHandler> {
+    let mut service = NoteService::new("./notes.db")?;
+    service.initialize_notes_service()?;
+    if let Some(passphrase) = passphrase_from_env()? {
+        service.unlock(&passphrase)?;
+    }
     let app = App::new();
-    Ok(AppHandler::new(app, db, f))
+    // a locked service starts the TUI on the passphrase prompt
+    Ok(AppHandler::new(app, service, f, config)?)
 }
 
+/// opens the notes database, unlocking it with the passphrase from the environment if encrypted
 pub fn get_note_service() -> ResultDynError<NoteService> {
-    let service = NoteService::new("./notes.db")?;
+    open_note_service("./notes.db")
+}
+
+/// like [`get_note_service`] for a database at any path
+pub fn open_note_service(db_path: impl AsRef<std::path::Path>) -> ResultDynError<NoteService> {
+    let mut service = NoteService::new(db_path)?;
+    service.initialize_notes_service()?;
+    if service.is_locked() {
+        let passphrase = passphrase_from_env()?.ok_or(NoteError::Locked)?;
+        service.unlock(&passphrase)?;
+    }
     Ok(service)
 }
+
+/// passphrase for encrypted notes from `NOTES_PASSPHRASE` or the file named by `NOTES_KEYFILE`
+pub fn passphrase_from_env() -> ResultDynError<Option<String>> {
+    if let Ok(passphrase) = std::env::var("NOTES_PASSPHRASE") {
+        return Ok(Some(passphrase));
+    }
+    match std::env::var("NOTES_KEYFILE") {
+        Ok(path) => {
+            let passphrase = std::fs::read_to_string(path)?;
+            Ok(Some(passphrase.trim_end_matches(['\r', '\n']).to_string()))
+        }
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,100 @@
+/// returns the targets of every `[[target]]` link in `text`, in order of appearance
+pub fn parse_links(text: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        match after.find("]]") {
+            Some(end) => {
+                let target = after[..end].trim();
+                if !target.is_empty() && !target.contains('\n') {
+                    links.push(target.to_string());
+                }
+                rest = &after[end + 2..];
+            }
+            None => break,
+        }
+    }
+    links
+}
+
+/// whether a target names a note by id: digits without a leading zero, so `[[007]]` is a title
+pub fn is_id(target: &str) -> bool {
+    !target.is_empty() && !target.starts_with('0') && target.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// whether a link target points at the note with this id or title, also registered as a
+/// SQL function for the SQLite store's backlinks
+pub fn matches(target: &str, note_id: usize, title: &str) -> bool {
+    if is_id(target) {
+        target.parse() == Ok(note_id)
+    } else {
+        target.eq_ignore_ascii_case(title.trim())
+    }
+}
+
+/// rewrites `[[old_title]]` links in `text` to point at `new_title`
+pub fn rewrite_links(text: &str, old_title: &str, new_title: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("]]") {
+            Some(end) => {
+                let target = &after[..end];
+                if target.trim().eq_ignore_ascii_case(old_title.trim()) {
+                    out.push_str(&format!("[[{}]]", new_title));
+                } else {
+                    out.push_str(&rest[start..start + 2 + end + 2]);
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_closed_single_line_targets_in_order() {
+        let text = "see [[One]] and [[ two ]], not [[]], [[a\nb]] or [[open";
+        assert_eq!(parse_links(text), ["One", "two"]);
+        assert!(parse_links("no links here").is_empty());
+    }
+
+    #[test]
+    fn only_digits_without_a_leading_zero_are_ids() {
+        assert!(is_id("12"));
+        for title in ["", "0", "007", "1a", "-1", "１２"] {
+            assert!(!is_id(title), "{title:?}");
+        }
+    }
+
+    #[test]
+    fn matches_by_id_or_by_title_ignoring_case() {
+        assert!(matches("7", 7, "seven"));
+        assert!(!matches("7", 8, "7"));
+        assert!(matches("007", 2, "007"));
+        assert!(!matches("007", 7, "seven"));
+        assert!(matches("shopping LIST", 3, " Shopping list "));
+    }
+
+    #[test]
+    fn rewrites_links_to_the_old_title_only() {
+        let text = "[[Old]] and [[ old ]] but [[Older]], [[7]] and [[Old";
+        assert_eq!(
+            rewrite_links(text, "Old", "New"),
+            "[[New]] and [[New]] but [[Older]], [[7]] and [[Old"
+        );
+        assert_eq!(rewrite_links("no links", "Old", "New"), "no links");
+    }
+}
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    attachment::Attachment,
+    error::{NoteError, NoteResult},
+    links,
+    store::{self, NoteStore},
+    Note,
+};
+
+/// keeps notes in memory, for tests and sessions that should not touch disk
+#[derive(Clone, Default)]
+pub struct MemoryRepository {
+    notes: BTreeMap<usize, Note>,
+    links: BTreeMap<usize, Vec<String>>,
+    meta: HashMap<String, Vec<u8>>,
+    attachments: BTreeMap<usize, Attachment>,
+    next_id: usize,
+    /// everything as it was at `begin`, restored by `rollback`
+    saved: Option<Box<MemoryRepository>>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> MemoryRepository {
+        MemoryRepository {
+            notes: BTreeMap::new(),
+            links: BTreeMap::new(),
+            meta: HashMap::new(),
+            attachments: BTreeMap::new(),
+            next_id: 1,
+            saved: None,
+        }
+    }
+}
+
+impl NoteStore for MemoryRepository {
+    fn initialize_db(&mut self) -> NoteResult<()> {
+        Ok(())
+    }
+
+    fn get_notes(&self) -> NoteResult<Vec<Note>> {
+        Ok(self.notes.values().cloned().collect())
+    }
+
+    fn get_note(&self, note_id: usize) -> NoteResult<Note> {
+        self.notes
+            .get(&note_id)
+            .cloned()
+            .ok_or(NoteError::NotFound(note_id))
+    }
+
+    fn add(&mut self, notes: Vec<Note>) -> NoteResult<Vec<usize>> {
+        let mut ids = Vec::with_capacity(notes.len());
+        let now = Utc::now();
+        for mut note in notes {
+            let id = self.next_id.max(1);
+            self.next_id = id + 1;
+            note.id = Some(id);
+            note.version = 1;
+            let created_at = note.created_at.unwrap_or(now);
+            note.created_at = Some(created_at);
+            note.updated_at = Some(note.updated_at.unwrap_or(created_at));
+            self.notes.insert(id, note);
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    fn update(&mut self, note_id: usize, new_note: Note) -> NoteResult<()> {
+        let note = self
+            .notes
+            .get_mut(&note_id)
+            .ok_or(NoteError::NotFound(note_id))?;
+        if note.version != new_note.version {
+            return Err(NoteError::Conflict {
+                id: note_id,
+                expected: new_note.version,
+                found: note.version,
+            });
+        }
+        *note = Note {
+            id: Some(note_id),
+            version: new_note.version + 1,
+            ..new_note
+        };
+        Ok(())
+    }
+
+    fn delete(&mut self, note_id: usize) -> NoteResult<Note> {
+        self.links.remove(&note_id);
+        self.attachments
+            .retain(|_, attachment| attachment.note_id != note_id);
+        self.notes
+            .remove(&note_id)
+            .ok_or(NoteError::NotFound(note_id))
+    }
+
+    fn search(&self, query: &str) -> NoteResult<Vec<Note>> {
+        Ok(self
+            .notes
+            .values()
+            .filter(|note| store::matches_search(note, query))
+            .cloned()
+            .collect())
+    }
+
+    fn set_links(&mut self, note_id: usize, targets: &[String]) -> NoteResult<()> {
+        self.links.insert(note_id, targets.to_vec());
+        Ok(())
+    }
+
+    fn get_links(&self, note_id: usize) -> NoteResult<Vec<String>> {
+        Ok(self.links.get(&note_id).cloned().unwrap_or_default())
+    }
+
+    fn get_backlinks(&self, note_id: usize) -> NoteResult<Vec<Note>> {
+        let note = self.get_note(note_id)?;
+        Ok(self
+            .links
+            .iter()
+            .filter(|(source, targets)| {
+                **source != note_id
+                    && targets
+                        .iter()
+                        .any(|target| links::matches(target, note_id, &note.title))
+            })
+            .filter_map(|(source, _)| self.notes.get(source).cloned())
+            .collect())
+    }
+
+    fn get_due_reminders(&self, now: DateTime<Utc>) -> NoteResult<Vec<Note>> {
+        let mut due: Vec<_> = self
+            .notes
+            .values()
+            .filter(|note| !note.completed && note.remind_at.is_some_and(|at| at <= now))
+            .cloned()
+            .collect();
+        due.sort_by_key(|note| note.remind_at);
+        Ok(due)
+    }
+
+    fn count_notes(&self) -> NoteResult<usize> {
+        Ok(self.notes.len())
+    }
+
+    fn count_created_per_day(&self, since: DateTime<Utc>) -> NoteResult<Vec<(NaiveDate, u64)>> {
+        let mut days: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+        for created in self.notes.values().filter_map(|note| note.created_at) {
+            if created >= since {
+                *days
+                    .entry(created.with_timezone(&Local).date_naive())
+                    .or_default() += 1;
+            }
+        }
+        Ok(days.into_iter().collect())
+    }
+
+    fn add_attachment(&mut self, mut attachment: Attachment) -> NoteResult<usize> {
+        if !self.notes.contains_key(&attachment.note_id) {
+            return Err(NoteError::NotFound(attachment.note_id));
+        }
+        let id = self.attachments.keys().next_back().map_or(1, |id| id + 1);
+        attachment.id = Some(id);
+        self.attachments.insert(id, attachment);
+        Ok(id)
+    }
+
+    fn get_attachments(&self, note_id: usize) -> NoteResult<Vec<Attachment>> {
+        Ok(self
+            .attachments
+            .values()
+            .filter(|attachment| attachment.note_id == note_id)
+            .map(|attachment| Attachment {
+                data: Vec::new(),
+                ..attachment.clone()
+            })
+            .collect())
+    }
+
+    fn get_attachment(&self, attachment_id: usize) -> NoteResult<Attachment> {
+        self.attachments
+            .get(&attachment_id)
+            .cloned()
+            .ok_or(NoteError::AttachmentNotFound(attachment_id))
+    }
+
+    fn delete_attachment(&mut self, attachment_id: usize) -> NoteResult<()> {
+        self.attachments
+            .remove(&attachment_id)
+            .map(|_| ())
+            .ok_or(NoteError::AttachmentNotFound(attachment_id))
+    }
+
+    fn get_meta(&self, key: &str) -> NoteResult<Option<Vec<u8>>> {
+        Ok(self.meta.get(key).cloned())
+    }
+
+    fn set_meta(&mut self, key: &str, value: &[u8]) -> NoteResult<()> {
+        self.meta.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn begin(&mut self) -> NoteResult<()> {
+        self.saved = Some(Box::new(MemoryRepository {
+            saved: None,
+            ..self.clone()
+        }));
+        Ok(())
+    }
+
+    fn commit(&mut self) -> NoteResult<()> {
+        self.saved = None;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> NoteResult<()> {
+        if let Some(saved) = self.saved.take() {
+            *self = *saved;
+        }
+        Ok(())
+    }
+}
@@ -10,11 +10,7 @@ use tui::{
 pub struct Render;
 
 impl Render {
-    pub fn render(
-        frame: &mut Frame<CrosstermBackend<Stdout>>,
-        area: Rect,
-        widget: impl TWidget,
-    ) -> () {
+    pub fn render(frame: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, widget: impl TWidget) {
         frame.render_widget(widget, area)
     }
     pub fn render_stateful(
@@ -1,17 +1,128 @@
-use rusqlite::{self, params, Connection, Result};
-use std::{fmt::Display, path::Path};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rusqlite::{self, functions::FunctionFlags, params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::{fmt::Display, path::Path, time::Duration};
+
+use crate::{
+    access::Scope,
+    attachment::Attachment,
+    error::{NoteError, NoteResult},
+    links, schedule,
+    store::{self, NoteStore},
+};
+
+/// how long a connection waits on another writer before giving up with `SQLITE_BUSY`
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NOTE_COLUMNS: &str =
+    "id, title, text, due_at, remind_at, completed, owner, scope, version, created_at, updated_at";
+
+fn timestamp(time: Option<DateTime<Utc>>) -> Option<i64> {
+    time.map(|time| time.timestamp())
+}
+
+fn from_timestamp(seconds: Option<i64>) -> Option<DateTime<Utc>> {
+    seconds.and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+}
+
+fn note_from_row(row: &Row) -> rusqlite::Result<Note> {
+    let mut note = Note::new(row.get(0)?, row.get(1)?, row.get(2)?);
+    note.due_at = from_timestamp(row.get(3)?);
+    note.remind_at = from_timestamp(row.get(4)?);
+    note.completed = row.get(5)?;
+    note.owner = row.get(6)?;
+    let scope: String = row.get(7)?;
+    note.scope = scope.parse().map_err(|err: String| {
+        rusqlite::Error::FromSqlConversionFailure(7, rusqlite::types::Type::Text, err.into())
+    })?;
+    note.version = row.get(8)?;
+    note.created_at = from_timestamp(row.get(9)?);
+    note.updated_at = from_timestamp(row.get(10)?);
+    Ok(note)
+}
 
 pub struct Repository {
     db: Connection,
 }
 
 impl Repository {
-    pub fn new(db_path: impl AsRef<Path>) -> Result<Repository> {
+    /// opens the database in WAL mode so readers on other connections don't wait on writers
+    pub fn new(db_path: impl AsRef<Path>) -> NoteResult<Repository> {
         let db = Connection::open(db_path)?;
+        db.busy_timeout(BUSY_TIMEOUT)?;
+        db.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        // `LIKE` treats `%` and `_` as wildcards and only folds ASCII case, so searches
+        // match through the same function the other stores use
+        db.create_scalar_function(
+            "contains_ignoring_case",
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let haystack: String = ctx.get(0)?;
+                let query: String = ctx.get(1)?;
+                Ok(store::contains_ignoring_case(&haystack, &query))
+            },
+        )?;
+        // backlinks resolve targets by the same id-or-title rule as the other stores
+        db.create_scalar_function(
+            "link_matches",
+            3,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let target: String = ctx.get(0)?;
+                let note_id: usize = ctx.get(1)?;
+                let title: String = ctx.get(2)?;
+                Ok(links::matches(&target, note_id, &title))
+            },
+        )?;
         Ok(Repository { db })
     }
 
-    pub fn initialize_db(&self) -> Result<()> {
+    fn query_notes(&self, sql: &str, params: impl rusqlite::Params) -> NoteResult<Vec<Note>> {
+        let mut stmt = self.db.prepare(sql)?;
+        let notes_iter = stmt.query_map(params, note_from_row)?;
+        let mut notes = Vec::new();
+        for note in notes_iter {
+            notes.push(note?);
+        }
+        Ok(notes)
+    }
+
+    fn table_exists(&self, table: &str) -> NoteResult<bool> {
+        Ok(self
+            .db
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// adds a column to an existing table unless an older schema already has it
+    fn ensure_column(&self, table: &str, column: &str, declaration: &str) -> NoteResult<()> {
+        let mut stmt = self.db.prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .iter()
+            .any(|name| name == column);
+        if !exists {
+            self.db.execute(
+                &format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, declaration
+                ),
+                (),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl NoteStore for Repository {
+    fn initialize_db(&mut self) -> NoteResult<()> {
         self.db.execute(
             "CREATE TABLE IF NOT EXISTS note (
                 id   INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -20,69 +131,369 @@ impl Repository {
             )",
             (),
         )?;
+        self.db.execute(
+            "CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value BLOB NOT NULL
+            )",
+            (),
+        )?;
+        self.db.execute(
+            "CREATE TABLE IF NOT EXISTS attachment (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                note_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                data BLOB NOT NULL
+            )",
+            (),
+        )?;
+        self.ensure_column("note", "due_at", "INTEGER")?;
+        self.ensure_column("note", "remind_at", "INTEGER")?;
+        self.ensure_column("note", "completed", "INTEGER NOT NULL DEFAULT 0")?;
+        self.ensure_column("note", "owner", "TEXT")?;
+        self.ensure_column("note", "scope", "TEXT NOT NULL DEFAULT 'local'")?;
+        self.ensure_column("note", "version", "INTEGER NOT NULL DEFAULT 1")?;
+        self.ensure_column("note", "created_at", "INTEGER")?;
+        self.ensure_column("note", "updated_at", "INTEGER")?;
+        let had_links = self.table_exists("link")?;
+        self.db.execute(
+            "CREATE TABLE IF NOT EXISTS link (
+                source_id INTEGER NOT NULL,
+                target TEXT NOT NULL
+            )",
+            (),
+        )?;
+        if !had_links {
+            // notes saved before links were tracked would otherwise never have backlinks
+            for note in self.get_notes()? {
+                let note_id = note.id.ok_or(NoteError::NotFound(0))?;
+                self.set_links(note_id, &links::parse_links(&note.text))?;
+            }
+        }
         Ok(())
     }
 
-    pub fn get_notes(&self) -> Result<Vec<Note>> {
-        let mut stmt = self.db.prepare("SELECT id, title, text FROM note")?;
-        let notes_iter = stmt.query_map([], |row| {
-            Ok(Note::new(row.get(0)?, row.get(1)?, row.get(2)?))
-        })?;
-        let mut notes = Vec::new();
-        for note in notes_iter {
-            notes.push(note?);
-        }
-        Ok(notes)
+    fn get_notes(&self) -> NoteResult<Vec<Note>> {
+        self.query_notes(&format!("SELECT {} FROM note", NOTE_COLUMNS), [])
     }
 
-    pub fn add(&mut self, notes: Vec<Note>) -> Result<()> {
-        let transaction = self.db.transaction()?;
+    fn add(&mut self, notes: Vec<Note>) -> NoteResult<Vec<usize>> {
+        let now = Utc::now();
+        // a savepoint so this also works inside `begin`
+        let transaction = self.db.savepoint()?;
+        let mut ids = Vec::with_capacity(notes.len());
         {
-            let mut stmt = transaction.prepare("INSERT INTO note (title, text) VALUES (?, ?)")?;
+            let mut stmt = transaction.prepare(
+                "INSERT INTO note (title, text, due_at, remind_at, completed, owner, scope,
+                                   created_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
             for note in &notes {
-                stmt.execute(params![note.title, note.text])?;
+                let created_at = note.created_at.unwrap_or(now);
+                ids.push(stmt.insert(params![
+                    note.title,
+                    note.text,
+                    timestamp(note.due_at),
+                    timestamp(note.remind_at),
+                    note.completed,
+                    note.owner,
+                    note.scope.to_string(),
+                    created_at.timestamp(),
+                    note.updated_at.unwrap_or(created_at).timestamp()
+                ])? as usize);
+            }
+        }
+        transaction.commit()?;
+        Ok(ids)
+    }
+
+    fn get_note(&self, note_id: usize) -> NoteResult<Note> {
+        self.db
+            .query_row(
+                &format!("SELECT {} FROM note WHERE id = ?1", NOTE_COLUMNS),
+                [note_id],
+                note_from_row,
+            )
+            .optional()?
+            .ok_or(NoteError::NotFound(note_id))
+    }
+
+    fn delete(&mut self, note_id: usize) -> NoteResult<Note> {
+        let note = self.get_note(note_id)?;
+        let transaction = self.db.savepoint()?;
+        transaction.execute("DELETE FROM link WHERE source_id = ?1", params![note_id])?;
+        transaction.execute(
+            "DELETE FROM attachment WHERE note_id = ?1",
+            params![note_id],
+        )?;
+        transaction.execute("DELETE FROM note WHERE id = ?1", params![note_id])?;
+        transaction.commit()?;
+        Ok(note)
+    }
+
+    fn update(&mut self, note_id: usize, new_note: Note) -> NoteResult<()> {
+        let changed = self.db.execute(
+            "UPDATE note SET title = ?1, text = ?2, due_at = ?3, remind_at = ?4, completed = ?5,
+                             owner = ?6, scope = ?7, version = version + 1, updated_at = ?10
+             WHERE id = ?8 AND version = ?9",
+            params![
+                new_note.title,
+                new_note.text,
+                timestamp(new_note.due_at),
+                timestamp(new_note.remind_at),
+                new_note.completed,
+                new_note.owner,
+                new_note.scope.to_string(),
+                note_id,
+                new_note.version,
+                timestamp(new_note.updated_at)
+            ],
+        )?;
+        if changed == 0 {
+            let found: Option<u64> = self
+                .db
+                .query_row("SELECT version FROM note WHERE id = ?", [note_id], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+            return Err(match found {
+                Some(found) => NoteError::Conflict {
+                    id: note_id,
+                    expected: new_note.version,
+                    found,
+                },
+                None => NoteError::NotFound(note_id),
+            });
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &str) -> NoteResult<Vec<Note>> {
+        self.query_notes(
+            &format!(
+                "SELECT {} FROM note
+                 WHERE contains_ignoring_case(title, ?1) OR contains_ignoring_case(text, ?1)",
+                NOTE_COLUMNS
+            ),
+            [query],
+        )
+    }
+
+    fn set_links(&mut self, note_id: usize, targets: &[String]) -> NoteResult<()> {
+        let transaction = self.db.savepoint()?;
+        transaction.execute("DELETE FROM link WHERE source_id = ?1", params![note_id])?;
+        {
+            let mut stmt =
+                transaction.prepare("INSERT INTO link (source_id, target) VALUES (?1, ?2)")?;
+            for target in targets {
+                stmt.execute(params![note_id, target])?;
             }
         }
         transaction.commit()?;
         Ok(())
     }
 
-    pub fn get_note(&self, note_id: usize) -> Result<Note> {
+    fn get_links(&self, note_id: usize) -> NoteResult<Vec<String>> {
         let mut stmt = self
             .db
-            .prepare("SELECT id, title, text FROM note WHERE id = ?1")?;
-        let mut note_iter = stmt.query_map([note_id], |row| {
-            Ok(Note::new(row.get(0)?, row.get(1)?, row.get(2)?))
-        })?;
-        note_iter
-            .next()
-            .ok_or_else(|| rusqlite::Error::QueryReturnedNoRows)?
-            .and_then(|r| Ok(r))
+            .prepare("SELECT target FROM link WHERE source_id = ?1 ORDER BY rowid")?;
+        let targets = stmt
+            .query_map([note_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(targets)
     }
 
-    pub fn delete(&self, note_id: usize) -> Result<Note> {
-        let note = self.get_note(note_id)?;
+    fn get_backlinks(&self, note_id: usize) -> NoteResult<Vec<Note>> {
+        self.query_notes(
+            "SELECT DISTINCT n.id, n.title, n.text, n.due_at, n.remind_at, n.completed,
+                    n.owner, n.scope, n.version, n.created_at, n.updated_at
+             FROM link l
+             JOIN note n ON n.id = l.source_id
+             JOIN note t ON t.id = ?1
+             WHERE n.id != t.id AND link_matches(l.target, t.id, t.title)",
+            [note_id],
+        )
+    }
+
+    fn get_due_reminders(&self, now: DateTime<Utc>) -> NoteResult<Vec<Note>> {
+        self.query_notes(
+            &format!(
+                "SELECT {} FROM note
+                 WHERE remind_at IS NOT NULL AND remind_at <= ?1 AND completed = 0
+                 ORDER BY remind_at",
+                NOTE_COLUMNS
+            ),
+            [now.timestamp()],
+        )
+    }
+
+    fn count_notes(&self) -> NoteResult<usize> {
+        let count: i64 = self
+            .db
+            .query_row("SELECT COUNT(*) FROM note", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// buckets by SQLite's `localtime`, which reads the same TZ setting as chrono's `Local`
+    fn count_created_per_day(&self, since: DateTime<Utc>) -> NoteResult<Vec<(NaiveDate, u64)>> {
+        let mut statement = self.db.prepare(
+            "SELECT date(created_at, 'unixepoch', 'localtime') AS day, COUNT(*) FROM note
+             WHERE created_at >= ?1
+             GROUP BY day
+             ORDER BY day",
+        )?;
+        let days = statement
+            .query_map([since.timestamp()], |row| {
+                let day: String = row.get(0)?;
+                let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d").map_err(|err| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        err.into(),
+                    )
+                })?;
+                Ok((day, row.get::<_, i64>(1)? as u64))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(days)
+    }
+
+    fn add_attachment(&mut self, attachment: Attachment) -> NoteResult<usize> {
+        self.get_note(attachment.note_id)?;
+        let id = self
+            .db
+            .prepare("INSERT INTO attachment (note_id, name, size, data) VALUES (?1, ?2, ?3, ?4)")?
+            .insert(params![
+                attachment.note_id,
+                attachment.name,
+                attachment.size,
+                attachment.data
+            ])?;
+        Ok(id as usize)
+    }
+
+    fn get_attachments(&self, note_id: usize) -> NoteResult<Vec<Attachment>> {
+        let mut stmt = self.db.prepare(
+            "SELECT id, note_id, name, size FROM attachment WHERE note_id = ?1 ORDER BY id",
+        )?;
+        let attachments = stmt
+            .query_map([note_id], |row| {
+                Ok(Attachment {
+                    id: row.get(0)?,
+                    note_id: row.get(1)?,
+                    name: row.get(2)?,
+                    size: row.get(3)?,
+                    data: Vec::new(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(attachments)
+    }
+
+    fn get_attachment(&self, attachment_id: usize) -> NoteResult<Attachment> {
         self.db
-            .execute("DELETE FROM note WHERE id = ?1", params![note_id])?;
-        Ok(note)
+            .query_row(
+                "SELECT id, note_id, name, size, data FROM attachment WHERE id = ?1",
+                [attachment_id],
+                |row| {
+                    Ok(Attachment {
+                        id: row.get(0)?,
+                        note_id: row.get(1)?,
+                        name: row.get(2)?,
+                        size: row.get(3)?,
+                        data: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?
+            .ok_or(NoteError::AttachmentNotFound(attachment_id))
+    }
+
+    fn delete_attachment(&mut self, attachment_id: usize) -> NoteResult<()> {
+        let changed = self
+            .db
+            .execute("DELETE FROM attachment WHERE id = ?1", [attachment_id])?;
+        if changed == 0 {
+            return Err(NoteError::AttachmentNotFound(attachment_id));
+        }
+        Ok(())
     }
 
-    // Placeholder for the update function if needed
-    pub fn update(&self, _note_id: usize, _new_note: Note) -> Result<()> {
+    fn get_meta(&self, key: &str) -> NoteResult<Option<Vec<u8>>> {
+        Ok(self
+            .db
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_meta(&mut self, key: &str, value: &[u8]) -> NoteResult<()> {
+        self.db.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
         Ok(())
     }
+
+    fn begin(&mut self) -> NoteResult<()> {
+        Ok(self.db.execute_batch("SAVEPOINT note_group")?)
+    }
+
+    fn commit(&mut self) -> NoteResult<()> {
+        Ok(self.db.execute_batch("RELEASE note_group")?)
+    }
+
+    fn rollback(&mut self) -> NoteResult<()> {
+        Ok(self
+            .db
+            .execute_batch("ROLLBACK TO note_group; RELEASE note_group")?)
+    }
+
+    fn data_version(&self) -> NoteResult<u64> {
+        Ok(self
+            .db
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?)
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Note {
     pub id: Option<usize>,
     pub title: String,
     pub text: String,
+    pub due_at: Option<DateTime<Utc>>,
+    /// when the next reminder fires, cleared once it has been delivered
+    pub remind_at: Option<DateTime<Utc>>,
+    pub completed: bool,
+    /// Discord user id of whoever created the note from the bot
+    pub owner: Option<String>,
+    pub scope: Scope,
+    /// bumped on every update, a write carrying an older version is rejected
+    pub version: u64,
+    /// `created_at` is stamped by the store and `updated_at` when the user edits the note,
+    /// both unknown for notes saved before they were kept
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
 }
 
 impl Note {
     pub fn new(id: Option<usize>, title: String, text: String) -> Note {
-        Note { id, title, text }
+        Note {
+            id,
+            title,
+            text,
+            due_at: None,
+            remind_at: None,
+            completed: false,
+            owner: None,
+            scope: Scope::Local,
+            version: 1,
+            created_at: None,
+            updated_at: None,
+        }
     }
 }
 
@@ -93,7 +504,11 @@ impl From<Note> for String {
 }
 impl Display for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "title: {}\n{}", self.title, self.text).unwrap();
-        Ok(())
+        writeln!(f, "title: {}", self.title)?;
+        if let Some(due_at) = self.due_at {
+            let status = if self.completed { " (done)" } else { "" };
+            writeln!(f, "due: {}{}", schedule::format_when(due_at), status)?;
+        }
+        writeln!(f, "{}", self.text)
     }
 }
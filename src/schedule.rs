@@ -0,0 +1,42 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// parses a span like `30m`, `2h`, `1d` or `1w`
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let unit = input.chars().last()?;
+    let amount: i64 = input[..input.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        's' => Some(Duration::seconds(amount)),
+        'm' => Some(Duration::minutes(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// parses `+30m`, `YYYY-MM-DD HH:MM` or `YYYY-MM-DD` (local time, 09:00) into an instant
+pub fn parse_when(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if let Some(span) = input.strip_prefix('+') {
+        return Some(now + parse_duration(span)?);
+    }
+    let naive = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(9, 0, 0)
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// formats an instant in local time the way [`parse_when`] accepts it
+pub fn format_when(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
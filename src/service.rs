@@ -1,42 +1,763 @@
-use rusqlite::Result as SqliteResult;
-use std::path::Path;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::path::{Path, PathBuf};
 
-use crate::repository::{Note, Repository};
+use crate::{
+    access::{Caller, Scope},
+    attachment::{self, Attachment},
+    crypto::{self, Cipher, WrappedKey},
+    dashboard::{self, Dashboard},
+    encrypted::EncryptedStore,
+    error::{NoteError, NoteResult},
+    export::{ExportedAttachment, ExportedNote},
+    journal::{self, DailyNote},
+    links,
+    memory::MemoryRepository,
+    repository::{Note, Repository},
+    store::NoteStore,
+    tags,
+    tasks::{self, Task, TaskFilter},
+};
+
+const SALT_META: &str = "crypto.salt";
+const KEY_META: &str = "crypto.key";
 
 pub struct NoteService {
-    repository: Repository,
+    repository: Box<dyn NoteStore>,
+    /// encrypted notes whose passphrase has not been given yet
+    locked: bool,
+    /// the store has been wrapped in an [`EncryptedStore`]
+    decrypting: bool,
+    /// database file, `None` for other stores
+    db_path: Option<PathBuf>,
 }
 
 impl NoteService {
     // Now takes an implementation of AsRef<Path> for flexibility and proper error handling
-    pub fn new<P: AsRef<Path>>(db_path: P) -> SqliteResult<Self> {
-        let repository = Repository::new(db_path)?;
-        Ok(NoteService { repository })
+    pub fn new<P: AsRef<Path>>(db_path: P) -> NoteResult<Self> {
+        let repository = Repository::new(&db_path)?;
+        Ok(NoteService {
+            db_path: Some(db_path.as_ref().to_path_buf()),
+            ..NoteService::with_store(Box::new(repository))
+        })
+    }
+
+    /// service backed by any storage implementation
+    pub fn with_store(repository: Box<dyn NoteStore>) -> Self {
+        NoteService {
+            repository,
+            locked: false,
+            decrypting: false,
+            db_path: None,
+        }
+    }
+
+    /// service whose notes only live as long as the process
+    pub fn in_memory() -> Self {
+        NoteService::with_store(Box::new(MemoryRepository::new()))
+    }
+
+    pub fn db_path(&self) -> Option<&Path> {
+        self.db_path.as_deref()
+    }
+
+    pub fn initialize_notes_service(&mut self) -> NoteResult<()> {
+        self.repository.initialize_db()?;
+        self.locked = !self.decrypting && self.is_encrypted()?;
+        Ok(())
+    }
+
+    fn store(&self) -> NoteResult<&dyn NoteStore> {
+        if self.locked {
+            return Err(NoteError::Locked);
+        }
+        Ok(self.repository.as_ref())
+    }
+
+    fn store_mut(&mut self) -> NoteResult<&mut dyn NoteStore> {
+        if self.locked {
+            return Err(NoteError::Locked);
+        }
+        Ok(self.repository.as_mut())
+    }
+
+    pub fn is_encrypted(&self) -> NoteResult<bool> {
+        Ok(self.repository.get_meta(KEY_META)?.is_some())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// differs from the last call once another process has written to the notes
+    pub fn data_version(&self) -> NoteResult<u64> {
+        self.repository.data_version()
+    }
+
+    fn wrapped_key(&self) -> NoteResult<WrappedKey> {
+        match (
+            self.repository.get_meta(SALT_META)?,
+            self.repository.get_meta(KEY_META)?,
+        ) {
+            (Some(salt), Some(wrapped)) => Ok(WrappedKey { salt, wrapped }),
+            _ => Err(NoteError::Crypto("notes are not encrypted".into())),
+        }
+    }
+
+    /// decrypts notes transparently from now on, a wrong passphrase leaves the service locked
+    pub fn unlock(&mut self, passphrase: &str) -> NoteResult<()> {
+        if !self.locked {
+            return Ok(());
+        }
+        let cipher = Cipher::unlock(passphrase, &self.wrapped_key()?)?;
+        self.wrap_store(cipher);
+        self.locked = false;
+        Ok(())
+    }
+
+    /// encrypts every existing note and keeps encrypting new ones
+    pub fn enable_encryption(&mut self, passphrase: &str) -> NoteResult<()> {
+        if self.is_encrypted()? {
+            return Err(NoteError::Crypto("notes are already encrypted".into()));
+        }
+        let notes = self.store()?.get_notes()?;
+        let (cipher, wrapped) = Cipher::generate(passphrase)?;
+        // the key and every re-encrypted row are saved together, a crash halfway
+        // can't leave plaintext behind in encrypted notes
+        self.repository.begin()?;
+        let inner = std::mem::replace(&mut self.repository, Box::new(MemoryRepository::new()));
+        let mut encrypted = EncryptedStore::new(inner, cipher);
+        encrypted.set_migrating(true);
+        let result = encrypt_all(&mut encrypted, &wrapped, notes);
+        encrypted.set_migrating(false);
+        match result.and_then(|()| encrypted.commit()) {
+            Ok(()) => {
+                self.repository = Box::new(encrypted);
+                self.decrypting = true;
+                Ok(())
+            }
+            Err(err) => {
+                self.repository = encrypted.into_inner();
+                self.repository.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// re-wraps the data key under a new passphrase without rewriting any note
+    pub fn change_passphrase(&mut self, old: &str, new: &str) -> NoteResult<()> {
+        let wrapped = Cipher::rekey(old, new, &self.wrapped_key()?)?;
+        // a new salt next to the old wrapped key would unlock with neither passphrase
+        self.atomically(|service| save_wrapped_key(service.repository.as_mut(), &wrapped))
+    }
+
+    /// runs `write` so that all of its changes are saved or none are
+    fn atomically<T>(
+        &mut self,
+        write: impl FnOnce(&mut NoteService) -> NoteResult<T>,
+    ) -> NoteResult<T> {
+        self.repository.begin()?;
+        match write(self).and_then(|value| self.repository.commit().map(|()| value)) {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                self.repository.rollback()?;
+                Err(err)
+            }
+        }
     }
 
-    pub fn initialize_notes_service(&self) -> SqliteResult<()> {
-        self.repository.initialize_db()
+    fn wrap_store(&mut self, cipher: Cipher) {
+        let inner = std::mem::replace(&mut self.repository, Box::new(MemoryRepository::new()));
+        self.repository = Box::new(EncryptedStore::new(inner, cipher));
+        self.decrypting = true;
     }
 
-    pub fn create_notes(&mut self, notes: Vec<Note>) -> SqliteResult<()> {
+    pub fn create_notes(&mut self, notes: Vec<Note>) -> NoteResult<Vec<usize>> {
         // You might add validation or transformation logic here before saving notes
-        self.repository.add(notes)
+        let targets: Vec<_> = notes
+            .iter()
+            .map(|note| links::parse_links(&note.text))
+            .collect();
+        let ids = self.store_mut()?.add(notes)?;
+        for (id, targets) in ids.iter().zip(targets) {
+            self.store_mut()?.set_links(*id, &targets)?;
+        }
+        Ok(ids)
+    }
+
+    pub fn list_all_notes(&self) -> NoteResult<Vec<Note>> {
+        self.store()?.get_notes()
+    }
+
+    pub fn fetch_note_by_id(&self, note_id: usize) -> NoteResult<Note> {
+        self.store()?.get_note(note_id)
+    }
+
+    /// notes titled with a date, newest first
+    pub fn journal(&self) -> NoteResult<Vec<Note>> {
+        let mut notes: Vec<(NaiveDate, Note)> = self
+            .store()?
+            .get_notes()?
+            .into_iter()
+            .filter_map(|note| Some((journal::date_of(&note)?, note)))
+            .collect();
+        notes.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Ok(notes.into_iter().map(|(_, note)| note).collect())
+    }
+
+    /// the note for `date`, created from the template body when there is none yet
+    pub fn open_daily_note(
+        &mut self,
+        date: NaiveDate,
+        template: Option<&str>,
+    ) -> NoteResult<DailyNote> {
+        let existing = self
+            .store()?
+            .get_notes()?
+            .into_iter()
+            .find(|note| journal::date_of(note) == Some(date));
+        if let Some(note) = existing {
+            return Ok(DailyNote {
+                note,
+                created: false,
+                cursor: None,
+            });
+        }
+        let expanded = journal::daily_text(date, template);
+        let note = Note::new(None, journal::title_of(date), expanded.text);
+        let ids = self.create_notes(vec![note])?;
+        Ok(DailyNote {
+            note: self.store()?.get_note(ids[0])?,
+            created: true,
+            cursor: expanded.cursor,
+        })
+    }
+
+    pub fn search_notes(&self, query: &str) -> NoteResult<Vec<Note>> {
+        self.store()?.search(query)
+    }
+
+    /// saves an edit unless someone else updated the note since `expected_version` was read
+    pub fn update_note(
+        &mut self,
+        note_id: usize,
+        expected_version: u64,
+        mut new_note: Note,
+    ) -> NoteResult<()> {
+        new_note.version = expected_version;
+        new_note.updated_at = Some(Utc::now());
+        // one group, so losing the race to another writer leaves links and backlinks untouched
+        self.atomically(|service| {
+            let old_note = service.store()?.get_note(note_id)?;
+            if old_note.version != expected_version {
+                return Err(NoteError::Conflict {
+                    id: note_id,
+                    expected: expected_version,
+                    found: old_note.version,
+                });
+            }
+            // found by the old title, so before the note gets its new one
+            let linking_notes = if old_note.title != new_note.title {
+                service.store()?.get_backlinks(note_id)?
+            } else {
+                Vec::new()
+            };
+            let targets = links::parse_links(&new_note.text);
+            let new_title = new_note.title.clone();
+            // the compare-and-swap goes first, nothing else is written if it fails
+            service.store_mut()?.update(note_id, new_note)?;
+            service.store_mut()?.set_links(note_id, &targets)?;
+            // keep `[[Old Title]]` links in other notes pointing at this note
+            for mut linking in linking_notes {
+                let text = links::rewrite_links(&linking.text, &old_note.title, &new_title);
+                if text == linking.text {
+                    continue;
+                }
+                let linking_id = linking.id.ok_or(NoteError::NotFound(note_id))?;
+                linking.text = text;
+                service
+                    .store_mut()?
+                    .set_links(linking_id, &links::parse_links(&linking.text))?;
+                service.store_mut()?.update(linking_id, linking)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// finds the note a `[[target]]` link points at, by id or by title
+    pub fn resolve_link(&self, target: &str) -> NoteResult<Option<Note>> {
+        if links::is_id(target) {
+            let Ok(id) = target.parse() else {
+                return Ok(None);
+            };
+            return match self.store()?.get_note(id) {
+                Ok(note) => Ok(Some(note)),
+                Err(NoteError::NotFound(_)) => Ok(None),
+                Err(err) => Err(err),
+            };
+        }
+        Ok(self
+            .store()?
+            .get_notes()?
+            .into_iter()
+            .find(|note| note.title.trim().eq_ignore_ascii_case(target)))
+    }
+
+    pub fn links_of(&self, note_id: usize) -> NoteResult<Vec<String>> {
+        self.store()?.get_links(note_id)
+    }
+
+    pub fn backlinks_of(&self, note_id: usize) -> NoteResult<Vec<Note>> {
+        self.store()?.get_backlinks(note_id)
+    }
+
+    pub fn delete_note(&mut self, note_id: usize) -> NoteResult<Note> {
+        self.store_mut()?.delete(note_id)
+    }
+
+    /// checklist items across every note that pass the filter
+    pub fn list_tasks(&self, filter: TaskFilter) -> NoteResult<Vec<Task>> {
+        Ok(self
+            .store()?
+            .get_notes()?
+            .iter()
+            .flat_map(tasks::parse_tasks)
+            .filter(|task| filter.accepts(task))
+            .collect())
+    }
+
+    /// totals, weekly activity, tags, recent edits and reminders for the Home tab
+    pub fn dashboard(&self, now: DateTime<Utc>) -> NoteResult<Dashboard> {
+        let store = self.store()?;
+        let created = store.count_created_per_day(dashboard::week_start(now))?;
+        Ok(Dashboard::new(
+            store.count_notes()?,
+            &created,
+            &store.get_notes()?,
+            now,
+        ))
+    }
+
+    /// checks or unchecks the item on `line` of the note and saves it
+    pub fn toggle_task(&mut self, note_id: usize, line: usize) -> NoteResult<Note> {
+        let mut note = self.store()?.get_note(note_id)?;
+        note.text = tasks::toggle_task(&note.text, line);
+        self.update_note(note_id, note.version, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    /// adds `#tag` to the note's text and saves it
+    pub fn tag_note(&mut self, note_id: usize, tag: &str) -> NoteResult<Note> {
+        let tag = tags::normalize(tag).map_err(NoteError::Invalid)?;
+        let mut note = self.store()?.get_note(note_id)?;
+        note.text = tags::add_tag(&note.text, &tag);
+        self.update_note(note_id, note.version, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    /// removes every `#tag` from the note's text and saves it
+    pub fn untag_note(&mut self, note_id: usize, tag: &str) -> NoteResult<Note> {
+        let tag = tags::normalize(tag).map_err(NoteError::Invalid)?;
+        let mut note = self.store()?.get_note(note_id)?;
+        note.text = tags::remove_tag(&note.text, &tag);
+        self.update_note(note_id, note.version, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    /// sets the due date, also scheduling a reminder for it if none is pending
+    pub fn set_due(&mut self, note_id: usize, due_at: Option<DateTime<Utc>>) -> NoteResult<Note> {
+        let mut note = self.store()?.get_note(note_id)?;
+        note.due_at = due_at;
+        if note.remind_at.is_none() {
+            note.remind_at = due_at;
+        }
+        self.store_mut()?.update(note_id, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    pub fn set_reminder(
+        &mut self,
+        note_id: usize,
+        remind_at: Option<DateTime<Utc>>,
+    ) -> NoteResult<Note> {
+        let mut note = self.store()?.get_note(note_id)?;
+        note.remind_at = remind_at;
+        note.completed = false;
+        self.store_mut()?.update(note_id, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    /// pushes the reminder back by `delay` from now
+    pub fn snooze(&mut self, note_id: usize, delay: Duration) -> NoteResult<Note> {
+        self.set_reminder(note_id, Some(Utc::now() + delay))
+    }
+
+    /// marks the note done and cancels any pending reminder
+    pub fn complete(&mut self, note_id: usize) -> NoteResult<Note> {
+        let mut note = self.store()?.get_note(note_id)?;
+        note.completed = true;
+        note.remind_at = None;
+        self.store_mut()?.update(note_id, note.clone())?;
+        note.version += 1;
+        Ok(note)
+    }
+
+    pub fn due_reminders(&self, now: DateTime<Utc>) -> NoteResult<Vec<Note>> {
+        self.store()?.get_due_reminders(now)
+    }
+
+    /// clears the reminder after it was delivered so it does not fire again
+    pub fn mark_reminded(&mut self, note_id: usize) -> NoteResult<()> {
+        let mut note = self.store()?.get_note(note_id)?;
+        note.remind_at = None;
+        self.store_mut()?.update(note_id, note)
+    }
+
+    /// stores the file at `path` with the note under its file name
+    pub fn attach_file(&mut self, note_id: usize, path: &Path) -> NoteResult<usize> {
+        let data = std::fs::read(path)?;
+        self.store_mut()?.add_attachment(Attachment::new(
+            note_id,
+            attachment::file_name(path),
+            data,
+        ))
+    }
+
+    pub fn list_attachments(&self, note_id: usize) -> NoteResult<Vec<Attachment>> {
+        self.store()?.get_attachments(note_id)
+    }
+
+    pub fn fetch_attachment(&self, attachment_id: usize) -> NoteResult<Attachment> {
+        self.store()?.get_attachment(attachment_id)
+    }
+
+    /// writes the attachment contents to `path`
+    pub fn extract_attachment(&self, attachment_id: usize, path: &Path) -> NoteResult<()> {
+        let attachment = self.store()?.get_attachment(attachment_id)?;
+        std::fs::write(path, attachment.data)?;
+        Ok(())
+    }
+
+    pub fn remove_attachment(&mut self, attachment_id: usize) -> NoteResult<()> {
+        self.store_mut()?.delete_attachment(attachment_id)
+    }
+
+    /// every note with its attachments, ready to be serialized
+    pub fn export_notes(&self) -> NoteResult<Vec<ExportedNote>> {
+        let store = self.store()?;
+        let mut exported = Vec::new();
+        for note in store.get_notes()? {
+            let note_id = note.id.ok_or(NoteError::NotFound(0))?;
+            let mut attachments = Vec::new();
+            for listed in store.get_attachments(note_id)? {
+                let attachment_id = listed.id.ok_or(NoteError::NotFound(note_id))?;
+                let attachment = store.get_attachment(attachment_id)?;
+                attachments.push(ExportedAttachment {
+                    name: attachment.name,
+                    data: crypto::to_hex(&attachment.data),
+                });
+            }
+            exported.push(ExportedNote {
+                id: note.id,
+                title: note.title,
+                text: note.text,
+                due_at: note.due_at,
+                remind_at: note.remind_at,
+                completed: note.completed,
+                owner: note.owner,
+                scope: Some(note.scope.to_string()),
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+                attachments,
+            });
+        }
+        Ok(exported)
+    }
+
+    /// creates the exported notes as new notes, returning their new ids
+    pub fn import_notes(&mut self, exported: Vec<ExportedNote>) -> NoteResult<Vec<usize>> {
+        let mut ids = Vec::with_capacity(exported.len());
+        for entry in exported {
+            let mut note = Note::new(None, entry.title, entry.text);
+            note.due_at = entry.due_at;
+            note.remind_at = entry.remind_at;
+            note.completed = entry.completed;
+            note.owner = entry.owner;
+            note.created_at = entry.created_at;
+            note.updated_at = entry.updated_at;
+            if let Some(scope) = entry.scope {
+                note.scope = scope.parse().map_err(NoteError::Invalid)?;
+            }
+            let note_id = self.create_notes(vec![note])?[0];
+            for attachment in entry.attachments {
+                let data = crypto::from_hex(&attachment.data).ok_or_else(|| {
+                    NoteError::Crypto(format!("attachment {} is not hex", attachment.name))
+                })?;
+                self.store_mut()?.add_attachment(Attachment::new(
+                    note_id,
+                    attachment.name,
+                    data,
+                ))?;
+            }
+            ids.push(note_id);
+        }
+        Ok(ids)
+    }
+
+    /// notes the Discord caller is allowed to see
+    pub fn list_visible_notes(&self, caller: &Caller) -> NoteResult<Vec<Note>> {
+        Ok(self
+            .store()?
+            .get_notes()?
+            .into_iter()
+            .filter(|note| caller.can_see(note))
+            .collect())
     }
 
-    pub fn list_all_notes(&self) -> SqliteResult<Vec<Note>> {
-        self.repository.get_notes()
+    /// the note if the caller may change it, hidden notes look like missing ones
+    pub fn fetch_modifiable(&self, caller: &Caller, note_id: usize) -> NoteResult<Note> {
+        match self.store()?.get_note(note_id) {
+            Ok(note) if caller.can_modify(&note) => Ok(note),
+            Ok(_) => Err(NoteError::NotFound(note_id)),
+            Err(err) => Err(err),
+        }
     }
 
-    pub fn fetch_note_by_id(&self, note_id: usize) -> SqliteResult<Note> {
-        self.repository.get_note(note_id)
+    /// creates a note owned by the caller in the given notebook
+    pub fn create_owned_note(
+        &mut self,
+        caller: &Caller,
+        scope: Scope,
+        title: String,
+        text: String,
+    ) -> NoteResult<usize> {
+        let mut note = Note::new(None, title, text);
+        note.owner = Some(caller.user.clone());
+        note.scope = scope;
+        Ok(self.create_notes(vec![note])?[0])
     }
 
-    pub fn update_note(&self, note_id: usize, new_note: Note) -> SqliteResult<()> {
-        // Additional logic before updating the note could be placed here
-        self.repository.update(note_id, new_note)
+    /// moves the caller's own note into another notebook
+    pub fn share_note(
+        &mut self,
+        caller: &Caller,
+        note_id: usize,
+        scope: Scope,
+    ) -> NoteResult<Note> {
+        let mut note = self.fetch_modifiable(caller, note_id)?;
+        if !caller.can_share(&note) {
+            return Err(NoteError::Forbidden(note_id));
+        }
+        note.scope = scope;
+        // not an edit of the text, so it doesn't count as recently edited
+        self.store_mut()?.update(note_id, note.clone())?;
+        note.version += 1;
+        Ok(note)
     }
+}
+
+fn save_wrapped_key(store: &mut dyn NoteStore, wrapped: &WrappedKey) -> NoteResult<()> {
+    store.set_meta(SALT_META, &wrapped.salt)?;
+    store.set_meta(KEY_META, &wrapped.wrapped)
+}
+
+/// stores the key and rewrites every note, its links and attachments through `store`
+fn encrypt_all(
+    store: &mut EncryptedStore,
+    wrapped: &WrappedKey,
+    notes: Vec<Note>,
+) -> NoteResult<()> {
+    save_wrapped_key(store, wrapped)?;
+    for note in notes {
+        let note_id = note.id.ok_or(NoteError::NotFound(0))?;
+        let targets = links::parse_links(&note.text);
+        store.update(note_id, note)?;
+        store.set_links(note_id, &targets)?;
+        for listed in store.get_attachments(note_id)? {
+            let attachment_id = listed.id.ok_or(NoteError::NotFound(note_id))?;
+            let attachment = store.get_attachment(attachment_id)?;
+            store.add_attachment(Attachment {
+                id: None,
+                ..attachment
+            })?;
+            store.delete_attachment(attachment_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_with(notes: &[(&str, &str)]) -> (NoteService, Vec<usize>) {
+        let mut service = NoteService::in_memory();
+        service.initialize_notes_service().unwrap();
+        let ids = service
+            .create_notes(
+                notes
+                    .iter()
+                    .map(|(title, text)| Note::new(None, title.to_string(), text.to_string()))
+                    .collect(),
+            )
+            .unwrap();
+        (service, ids)
+    }
+
+    #[test]
+    fn renaming_a_note_rewrites_links_to_it() {
+        let (mut service, ids) = service_with(&[
+            ("Old", ""),
+            ("linker", "see [[old]] and [[Other]]"),
+            ("007", "numeric title"),
+            ("numeric linker", "[[007]]"),
+        ]);
+        let mut renamed = service.fetch_note_by_id(ids[0]).unwrap();
+        let version = renamed.version;
+        renamed.title = "New".into();
+        service.update_note(ids[0], version, renamed).unwrap();
+
+        let linker = service.fetch_note_by_id(ids[1]).unwrap();
+        assert_eq!(linker.text, "see [[New]] and [[Other]]");
+        assert_eq!(service.links_of(ids[1]).unwrap(), ["New", "Other"]);
+        assert_eq!(service.backlinks_of(ids[0]).unwrap()[0].id, Some(ids[1]));
+
+        let mut numeric = service.fetch_note_by_id(ids[2]).unwrap();
+        let version = numeric.version;
+        numeric.title = "008".into();
+        service.update_note(ids[2], version, numeric).unwrap();
+        assert_eq!(service.fetch_note_by_id(ids[3]).unwrap().text, "[[008]]");
+    }
+
+    fn titles(service: &NoteService) -> NoteResult<Vec<String>> {
+        Ok(service
+            .list_all_notes()?
+            .into_iter()
+            .map(|note| note.title)
+            .collect())
+    }
+
+    /// a memory store with notes encrypted under `passphrase`, as a freshly opened service
+    /// finds it
+    fn locked_service(passphrase: &str, notes: &[&str]) -> NoteService {
+        let mut memory = MemoryRepository::new();
+        memory
+            .add(
+                notes
+                    .iter()
+                    .map(|title| Note::new(None, title.to_string(), String::new()))
+                    .collect(),
+            )
+            .unwrap();
+        let notes = memory.get_notes().unwrap();
+        let (cipher, wrapped) = Cipher::generate(passphrase).unwrap();
+        let mut encrypted = EncryptedStore::new(Box::new(memory), cipher);
+        encrypted.set_migrating(true);
+        encrypt_all(&mut encrypted, &wrapped, notes).unwrap();
+        let mut service = NoteService::with_store(encrypted.into_inner());
+        service.initialize_notes_service().unwrap();
+        service
+    }
+
+    #[test]
+    fn a_wrong_passphrase_leaves_the_notes_for_the_right_one() {
+        let mut service = locked_service("right", &["first", "second"]);
+        assert!(service.is_locked());
+        assert!(matches!(titles(&service), Err(NoteError::Locked)));
+
+        assert!(matches!(
+            service.unlock("wrong"),
+            Err(NoteError::WrongPassphrase)
+        ));
+        assert!(service.is_locked());
+
+        service.unlock("right").unwrap();
+        assert_eq!(titles(&service).unwrap(), ["first", "second"]);
+    }
+
+    #[test]
+    fn encrypting_keeps_notes_readable_and_rekey_swaps_the_passphrase() {
+        let (mut service, _) = service_with(&[("first", "[[second]]"), ("second", "")]);
+        service.enable_encryption("old").unwrap();
+        assert!(service.is_encrypted().unwrap());
+        assert!(!service.is_locked());
+        assert_eq!(titles(&service).unwrap(), ["first", "second"]);
+        assert_eq!(service.backlinks_of(2).unwrap().len(), 1);
+        assert!(service.enable_encryption("again").is_err());
+
+        assert!(matches!(
+            service.change_passphrase("wrong", "new"),
+            Err(NoteError::WrongPassphrase)
+        ));
+        service.change_passphrase("old", "new").unwrap();
+        let wrapped = service.wrapped_key().unwrap();
+        assert!(matches!(
+            Cipher::unlock("old", &wrapped),
+            Err(NoteError::WrongPassphrase)
+        ));
+        assert!(Cipher::unlock("new", &wrapped).is_ok());
+    }
+
+    #[test]
+    fn a_failed_migration_leaves_the_notes_in_plaintext() {
+        let (mut service, ids) = service_with(&[("first", "text"), ("second", "")]);
+        // a plaintext name that looks like ciphertext can't be read back mid-migration
+        service
+            .repository
+            .add_attachment(Attachment::new(
+                ids[1],
+                "enc:v1:zz".into(),
+                b"data".to_vec(),
+            ))
+            .unwrap();
+
+        assert!(matches!(
+            service.enable_encryption("secret"),
+            Err(NoteError::Crypto(_))
+        ));
+        assert!(!service.is_encrypted().unwrap());
+        assert_eq!(titles(&service).unwrap(), ["first", "second"]);
+        assert_eq!(service.fetch_note_by_id(ids[0]).unwrap().text, "text");
+        let attachment = service.list_attachments(ids[1]).unwrap().remove(0);
+        assert_eq!(attachment.name, "enc:v1:zz");
+        let attachment = service.fetch_attachment(attachment.id.unwrap()).unwrap();
+        assert_eq!(attachment.data, b"data");
+    }
+
+    #[test]
+    fn opening_a_day_reuses_its_note_and_the_journal_lists_newest_first() {
+        let (mut service, ids) = service_with(&[
+            ("2024-03-08", "older"),
+            ("not a day", ""),
+            ("2024-03-10", "newer"),
+        ]);
+        let day = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let daily = service.open_daily_note(day, Some("# {{title}}")).unwrap();
+        assert!(!daily.created);
+        assert_eq!(daily.note.id, Some(ids[2]));
+        assert_eq!(daily.note.text, "newer");
+
+        let day = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+        let daily = service
+            .open_daily_note(day, Some("# {{title}}\n{{cursor}}"))
+            .unwrap();
+        assert!(daily.created);
+        assert_eq!(daily.note.text, "# 2024-03-09\n");
+        assert_eq!(daily.cursor, Some((2, 1)));
+        let again = service.open_daily_note(day, None).unwrap();
+        assert!(!again.created);
+        assert_eq!(again.note.id, daily.note.id);
 
-    pub fn delete_note(&self, note_id: usize) -> SqliteResult<Note> {
-        self.repository.delete(note_id)
+        let journal: Vec<String> = service
+            .journal()
+            .unwrap()
+            .into_iter()
+            .map(|note| note.title)
+            .collect();
+        assert_eq!(journal, ["2024-03-10", "2024-03-09", "2024-03-08"]);
     }
 }
@@ -0,0 +1,204 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{attachment::Attachment, error::NoteResult, Note};
+
+/// literal substring match ignoring case, the same for every backend: `%` and `_` are plain
+/// characters and non-ASCII letters fold too
+pub fn contains_ignoring_case(haystack: &str, query: &str) -> bool {
+    haystack.to_lowercase().contains(&query.to_lowercase())
+}
+
+pub fn matches_search(note: &Note, query: &str) -> bool {
+    contains_ignoring_case(&note.title, query) || contains_ignoring_case(&note.text, query)
+}
+
+/// storage backend for notes, implemented by the SQLite [`crate::Repository`]
+/// and the in-memory [`crate::MemoryRepository`]
+pub trait NoteStore: Send {
+    /// creates whatever schema the backend needs, safe to call more than once
+    fn initialize_db(&mut self) -> NoteResult<()>;
+    fn get_notes(&self) -> NoteResult<Vec<Note>>;
+    fn get_note(&self, note_id: usize) -> NoteResult<Note>;
+    /// inserts the notes and returns their new ids in the same order
+    fn add(&mut self, notes: Vec<Note>) -> NoteResult<Vec<usize>>;
+    /// writes the note if it is still at `new_note.version`, failing with
+    /// [`NoteError::Conflict`](crate::NoteError::Conflict) otherwise, and bumps the version
+    ///
+    /// `updated_at` is saved as given, only edits by the user move it
+    fn update(&mut self, note_id: usize, new_note: Note) -> NoteResult<()>;
+    fn delete(&mut self, note_id: usize) -> NoteResult<Note>;
+    /// notes whose title or text contains `query` as [`matches_search`] decides
+    fn search(&self, query: &str) -> NoteResult<Vec<Note>>;
+    /// replaces the outgoing `[[link]]` targets stored for a note
+    fn set_links(&mut self, note_id: usize, targets: &[String]) -> NoteResult<()>;
+    fn get_links(&self, note_id: usize) -> NoteResult<Vec<String>>;
+    /// notes whose links point at this note by id or title
+    fn get_backlinks(&self, note_id: usize) -> NoteResult<Vec<Note>>;
+    /// unfinished notes whose reminder time is at or before `now`
+    fn get_due_reminders(&self, now: DateTime<Utc>) -> NoteResult<Vec<Note>>;
+    fn count_notes(&self) -> NoteResult<usize>;
+    /// notes created on each local day from `since` on, oldest first, days without any left out
+    fn count_created_per_day(&self, since: DateTime<Utc>) -> NoteResult<Vec<(NaiveDate, u64)>>;
+    fn add_attachment(&mut self, attachment: Attachment) -> NoteResult<usize>;
+    /// attachments of a note without their contents
+    fn get_attachments(&self, note_id: usize) -> NoteResult<Vec<Attachment>>;
+    /// a single attachment including its contents
+    fn get_attachment(&self, attachment_id: usize) -> NoteResult<Attachment>;
+    fn delete_attachment(&mut self, attachment_id: usize) -> NoteResult<()>;
+    /// small key/value settings stored next to the notes, such as the wrapped encryption key
+    fn get_meta(&self, key: &str) -> NoteResult<Option<Vec<u8>>>;
+    fn set_meta(&mut self, key: &str, value: &[u8]) -> NoteResult<()>;
+    /// starts a group of writes that [`commit`](NoteStore::commit) saves together and
+    /// [`rollback`](NoteStore::rollback) throws away, groups don't nest
+    fn begin(&mut self) -> NoteResult<()>;
+    fn commit(&mut self) -> NoteResult<()>;
+    fn rollback(&mut self) -> NoteResult<()>;
+    /// changes whenever another connection commits, backends nobody else writes to keep it fixed
+    fn data_version(&self) -> NoteResult<u64> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryRepository, NoteError, Repository};
+
+    /// every backend, fresh and initialized, named for assertion messages
+    fn stores() -> Vec<(&'static str, Box<dyn NoteStore>)> {
+        let mut sqlite = Repository::new(":memory:").unwrap();
+        sqlite.initialize_db().unwrap();
+        let mut memory = MemoryRepository::new();
+        memory.initialize_db().unwrap();
+        vec![("sqlite", Box::new(sqlite)), ("memory", Box::new(memory))]
+    }
+
+    fn titles(notes: Vec<Note>) -> Vec<String> {
+        let mut titles: Vec<String> = notes.into_iter().map(|note| note.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn search_is_literal_and_ignores_case() {
+        for (name, mut store) in stores() {
+            store
+                .add(vec![
+                    Note::new(None, "100% done".into(), String::new()),
+                    Note::new(None, "snake_case".into(), String::new()),
+                    Note::new(None, "plain".into(), "Ärger im Büro".into()),
+                ])
+                .unwrap();
+            assert_eq!(titles(store.search("%").unwrap()), ["100% done"], "{name}");
+            assert_eq!(titles(store.search("_").unwrap()), ["snake_case"], "{name}");
+            assert_eq!(
+                titles(store.search("DONE").unwrap()),
+                ["100% done"],
+                "{name}"
+            );
+            assert_eq!(titles(store.search("ärger").unwrap()), ["plain"], "{name}");
+            assert_eq!(store.search("").unwrap().len(), 3, "{name}");
+        }
+    }
+
+    #[test]
+    fn update_only_wins_at_the_current_version() {
+        for (name, mut store) in stores() {
+            let id = store
+                .add(vec![Note::new(None, "first".into(), String::new())])
+                .unwrap()[0];
+            let mut note = store.get_note(id).unwrap();
+            assert_eq!(note.version, 1, "{name}");
+
+            note.title = "second".into();
+            store.update(id, note.clone()).unwrap();
+            let saved = store.get_note(id).unwrap();
+            assert_eq!(
+                (saved.title.as_str(), saved.version),
+                ("second", 2),
+                "{name}"
+            );
+
+            note.title = "stale".into();
+            match store.update(id, note.clone()) {
+                Err(NoteError::Conflict {
+                    id: conflict,
+                    expected: 1,
+                    found: 2,
+                }) => assert_eq!(conflict, id, "{name}"),
+                other => panic!("{name}: expected a conflict, got {other:?}"),
+            }
+            assert_eq!(store.get_note(id).unwrap().title, "second", "{name}");
+            assert!(
+                matches!(store.update(id + 1, note), Err(NoteError::NotFound(_))),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn delete_removes_links_and_attachments() {
+        for (name, mut store) in stores() {
+            let ids = store
+                .add(vec![
+                    Note::new(None, "gone".into(), String::new()),
+                    Note::new(None, "kept".into(), String::new()),
+                ])
+                .unwrap();
+            let (gone, kept) = (ids[0], ids[1]);
+            store.set_links(gone, &["kept".to_string()]).unwrap();
+            let attachment = store
+                .add_attachment(Attachment::new(gone, "a.txt".into(), b"data".to_vec()))
+                .unwrap();
+            assert_eq!(store.get_backlinks(kept).unwrap().len(), 1, "{name}");
+
+            assert_eq!(store.delete(gone).unwrap().title, "gone", "{name}");
+            assert!(store.get_links(gone).unwrap().is_empty(), "{name}");
+            assert!(store.get_backlinks(kept).unwrap().is_empty(), "{name}");
+            assert!(store.get_attachments(gone).unwrap().is_empty(), "{name}");
+            assert!(
+                matches!(
+                    store.get_attachment(attachment),
+                    Err(NoteError::AttachmentNotFound(_))
+                ),
+                "{name}"
+            );
+            assert!(
+                matches!(store.delete(gone), Err(NoteError::NotFound(_))),
+                "{name}"
+            );
+            assert_eq!(titles(store.get_notes().unwrap()), ["kept"], "{name}");
+        }
+    }
+
+    #[test]
+    fn backlinks_resolve_ids_and_titles_alike() {
+        for (name, mut store) in stores() {
+            let ids = store
+                .add(
+                    ["Target", "007", "by id", "by title", "leading zero"]
+                        .iter()
+                        .map(|title| Note::new(None, title.to_string(), String::new()))
+                        .collect(),
+                )
+                .unwrap();
+            let (target, numeric) = (ids[0], ids[1]);
+            store.set_links(ids[2], &[target.to_string()]).unwrap();
+            store
+                .set_links(ids[3], &["TARGET".to_string(), "007".to_string()])
+                .unwrap();
+            store.set_links(ids[4], &[format!("0{target}")]).unwrap();
+
+            assert_eq!(
+                titles(store.get_backlinks(target).unwrap()),
+                ["by id", "by title"],
+                "{name}"
+            );
+            assert_eq!(
+                titles(store.get_backlinks(numeric).unwrap()),
+                ["by title"],
+                "{name}"
+            );
+        }
+    }
+}
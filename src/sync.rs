@@ -0,0 +1,589 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{NoteError, NoteResult},
+    repository::Note,
+    service::NoteService,
+    tags,
+};
+
+/// what the last sync saw on both sides, kept next to the Markdown files
+const STATE_FILE: &str = ".notes-sync.json";
+/// copy of the database side written next to a file that changed on both sides
+const CONFLICT_SUFFIX: &str = ".conflict.md";
+
+/// which side wins when a note changed in the database and on disk since the last sync
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prefer {
+    /// leave both sides alone and report the conflict
+    Neither,
+    Database,
+    Disk,
+}
+
+#[derive(Debug)]
+pub struct Conflict {
+    /// `None` for a new file that could not be made into a note
+    pub note_id: Option<usize>,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// what one sync pass did, by note id
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// database changes written out to files
+    pub written: Vec<usize>,
+    /// file edits applied to the database
+    pub applied: Vec<usize>,
+    /// notes created from new files
+    pub created: Vec<usize>,
+    /// files removed because their note was deleted
+    pub removed_files: Vec<usize>,
+    /// notes deleted because their file was removed
+    pub removed_notes: Vec<usize>,
+    /// files renamed on disk, or after their note's title changed
+    pub renamed: Vec<usize>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.written.is_empty()
+            && self.applied.is_empty()
+            && self.created.is_empty()
+            && self.removed_files.is_empty()
+            && self.removed_notes.is_empty()
+            && self.renamed.is_empty()
+            && self.conflicts.is_empty()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    file: String,
+    /// hash of the note as rendered at the last sync
+    db: u64,
+    /// hash of the file contents at the last sync
+    disk: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    notes: BTreeMap<usize, Entry>,
+}
+
+/// FNV-1a, stable across runs and toolchains unlike the std hasher
+fn hash(content: &str) -> u64 {
+    content.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// file contents for a note, front-matter first
+pub fn render_markdown(note: &Note) -> String {
+    let mut content = String::from("---\n");
+    content.push_str(&format!("id: {}\n", note.id.unwrap_or_default()));
+    content.push_str(&format!("title: {}\n", note.title));
+    if let Some(due_at) = note.due_at {
+        content.push_str(&format!("due: {}\n", due_at.to_rfc3339()));
+    }
+    if let Some(remind_at) = note.remind_at {
+        content.push_str(&format!("remind: {}\n", remind_at.to_rfc3339()));
+    }
+    content.push_str(&format!("completed: {}\n", note.completed));
+    content.push_str(&format!("scope: {}\n", note.scope));
+    if let Some(created_at) = note.created_at {
+        content.push_str(&format!("created: {}\n", created_at.to_rfc3339()));
+    }
+    if let Some(updated_at) = note.updated_at {
+        content.push_str(&format!("updated: {}\n", updated_at.to_rfc3339()));
+    }
+    let tags = tags::parse_tags(&note.text);
+    if !tags.is_empty() {
+        content.push_str(&format!("tags: {}\n", tags.join(", ")));
+    }
+    content.push_str("---\n");
+    content.push_str(&note.text);
+    content
+}
+
+/// writes every note to `dir` as `notes sync` would, without tracking them for syncing
+pub fn export_markdown(notes: &[Note], dir: &Path) -> NoteResult<usize> {
+    fs::create_dir_all(dir)?;
+    for note in notes {
+        fs::write(dir.join(file_name(note)), render_markdown(note))?;
+    }
+    Ok(notes.len())
+}
+
+/// note fields read from a Markdown file
+struct FileNote {
+    id: Option<usize>,
+    title: Option<String>,
+    due_at: Option<DateTime<Utc>>,
+    remind_at: Option<DateTime<Utc>>,
+    completed: bool,
+    text: String,
+}
+
+/// files saved with Windows line endings read the same as any other
+fn parse_markdown(content: &str) -> FileNote {
+    let content = content.replace("\r\n", "\n");
+    let mut note = FileNote {
+        id: None,
+        title: None,
+        due_at: None,
+        remind_at: None,
+        completed: false,
+        text: content.to_string(),
+    };
+    let rest = match content.strip_prefix("---\n") {
+        Some(rest) => rest,
+        None => return note,
+    };
+    let (front, text) = match rest.split_once("\n---\n") {
+        Some((front, text)) => (front, text),
+        None => match rest.strip_suffix("\n---") {
+            Some(front) => (front, ""),
+            None => return note,
+        },
+    };
+    let when = |value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|when| when.with_timezone(&Utc))
+    };
+    for line in front.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "id" => note.id = value.parse().ok(),
+            "title" if !value.is_empty() => note.title = Some(value.to_string()),
+            "due" => note.due_at = when(value),
+            "remind" => note.remind_at = when(value),
+            "completed" => note.completed = value == "true",
+            // the database keeps the timestamps and tags come from the `#tags` in the text,
+            // so editing these lines changes nothing
+            "created" | "updated" | "tags" => (),
+            _ => (),
+        }
+    }
+    note.text = text.to_string();
+    note
+}
+
+/// `my-title-3.md`, the id keeps names unique when titles repeat
+fn file_name(note: &Note) -> String {
+    let slug: String = note
+        .title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let id = note.id.unwrap_or_default();
+    if slug.is_empty() {
+        format!("note-{id}.md")
+    } else {
+        format!("{slug}-{id}.md")
+    }
+}
+
+fn load_state(dir: &Path) -> NoteResult<State> {
+    match fs::read_to_string(dir.join(STATE_FILE)) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|err| NoteError::Invalid(format!("{STATE_FILE}: {err}"))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn save_state(dir: &Path, state: &State) -> NoteResult<()> {
+    let content =
+        serde_json::to_string_pretty(state).map_err(|err| NoteError::Invalid(err.to_string()))?;
+    Ok(fs::write(dir.join(STATE_FILE), content)?)
+}
+
+/// Markdown files in the directory by file name, without conflict copies
+fn scan(dir: &Path) -> NoteResult<BTreeMap<String, String>> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(".md") || name.ends_with(CONFLICT_SUFFIX) || !entry.path().is_file() {
+            continue;
+        }
+        files.insert(name, fs::read_to_string(entry.path())?);
+    }
+    Ok(files)
+}
+
+struct Sync<'a> {
+    service: &'a mut NoteService,
+    dir: &'a Path,
+    prefer: Prefer,
+    state: State,
+    report: SyncReport,
+}
+
+impl Sync<'_> {
+    /// writes the database side out, renaming files that still carry a generated name
+    fn write_note(&mut self, note: &Note, current: Option<&str>) -> NoteResult<()> {
+        let id = note.id.unwrap_or_default();
+        let content = render_markdown(note);
+        let generated = current.is_none_or(|name| name.ends_with(&format!("-{id}.md")));
+        let file = match current {
+            Some(name) if !generated => name.to_string(),
+            _ => file_name(note),
+        };
+        if let Some(old) = current.filter(|old| *old != file) {
+            fs::rename(self.dir.join(old), self.dir.join(&file))?;
+            self.report.renamed.push(id);
+        }
+        fs::write(self.dir.join(&file), &content)?;
+        let hash = hash(&content);
+        self.state.notes.insert(
+            id,
+            Entry {
+                file,
+                db: hash,
+                disk: hash,
+            },
+        );
+        Ok(())
+    }
+
+    /// applies a file edit to the database, the note keeps its scope and owner
+    fn apply_file(&mut self, mut note: Note, file: &str, content: &str) -> NoteResult<()> {
+        let id = note.id.unwrap_or_default();
+        let version = note.version;
+        let parsed = parse_markdown(content);
+        note.title = parsed.title.unwrap_or(note.title);
+        note.text = parsed.text;
+        note.due_at = parsed.due_at;
+        note.remind_at = parsed.remind_at;
+        note.completed = parsed.completed;
+        self.service.update_note(id, version, note)?;
+        let updated = self.service.fetch_note_by_id(id)?;
+        self.state.notes.insert(
+            id,
+            Entry {
+                file: file.to_string(),
+                db: hash(&render_markdown(&updated)),
+                disk: hash(content),
+            },
+        );
+        Ok(())
+    }
+
+    fn conflict(&mut self, note_id: usize, file: &str, reason: &str) {
+        self.report.conflicts.push(Conflict {
+            note_id: Some(note_id),
+            path: self.dir.join(file),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// reports what could not be synced, the state keeps its last entry so the next
+    /// pass tries again
+    fn failed(&mut self, note_id: Option<usize>, file: &str, err: NoteError) {
+        self.report.conflicts.push(Conflict {
+            note_id,
+            path: self.dir.join(file),
+            reason: format!("could not sync: {err}"),
+        });
+    }
+
+    fn run(&mut self) -> NoteResult<()> {
+        let notes: BTreeMap<usize, Note> = self
+            .service
+            .list_all_notes()?
+            .into_iter()
+            .filter_map(|note| Some((note.id?, note)))
+            .collect();
+        let files = scan(self.dir)?;
+        // files are matched to notes by the id in their front-matter, so renames on disk keep their note
+        let mut by_id: HashMap<usize, (String, String)> = HashMap::new();
+        let mut new_files = Vec::new();
+        for (name, content) in files {
+            match parse_markdown(&content).id {
+                Some(id) if !by_id.contains_key(&id) && self.known(id, &notes) => {
+                    by_id.insert(id, (name, content));
+                }
+                _ => new_files.push((name, content)),
+            }
+        }
+
+        // a file whose front-matter stopped parsing looks new, so its note looks deleted
+        let unmatched = !new_files.is_empty();
+        let mut held = false;
+        for (&id, note) in &notes {
+            let entry = self.state.notes.get(&id).cloned();
+            let file = by_id.remove(&id);
+            let name = match (&file, &entry) {
+                (Some((name, _)), _) => name.clone(),
+                (None, Some(entry)) => entry.file.clone(),
+                (None, None) => file_name(note),
+            };
+            // one note failing, say on a version conflict with the TUI, doesn't stop the others
+            if let Err(err) = self.sync_note(note, entry, file, unmatched, &mut held) {
+                self.failed(Some(id), &name, err);
+            }
+        }
+
+        // notes deleted from the database since the last sync
+        for (id, (name, content)) in by_id {
+            let entry = match self.state.notes.get(&id) {
+                Some(entry) => entry.clone(),
+                None => continue,
+            };
+            if hash(&content) == entry.disk || self.prefer == Prefer::Database {
+                if let Err(err) = fs::remove_file(self.dir.join(&name)) {
+                    self.failed(Some(id), &name, err.into());
+                    continue;
+                }
+                self.state.notes.remove(&id);
+                self.report.removed_files.push(id);
+            } else if self.prefer == Prefer::Disk {
+                // the edited file comes back as a new note
+                self.state.notes.remove(&id);
+                new_files.push((name, content));
+            } else {
+                self.conflict(id, &name, "note deleted but file changed");
+            }
+        }
+        // entries whose note and file are both gone
+        let notes_ids: Vec<usize> = notes.keys().copied().collect();
+        self.state
+            .notes
+            .retain(|id, entry| notes_ids.contains(id) || self.dir.join(&entry.file).exists());
+
+        if held {
+            // they stay as they are until the missing files are sorted out
+            new_files.clear();
+        }
+        for (name, content) in new_files {
+            if let Err(err) = self.create_from_file(&name, &content) {
+                self.failed(None, &name, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// brings one note and its file in line, `held` is set when a deletion waits on unmatched files
+    fn sync_note(
+        &mut self,
+        note: &Note,
+        entry: Option<Entry>,
+        file: Option<(String, String)>,
+        unmatched: bool,
+        held: &mut bool,
+    ) -> NoteResult<()> {
+        let id = note.id.unwrap_or_default();
+        match (entry, file) {
+            (None, None) => {
+                self.write_note(note, None)?;
+                self.report.written.push(id);
+            }
+            (None, Some((name, content))) => {
+                // first sync against an existing file, treat it as a conflict unless identical
+                if content == render_markdown(note) {
+                    self.write_note(note, Some(&name))?;
+                } else {
+                    self.resolve(note, &name, &content)?;
+                }
+            }
+            (Some(entry), None) => {
+                let db_changed = hash(&render_markdown(note)) != entry.db;
+                if db_changed {
+                    self.conflict(id, &entry.file, "file deleted but note changed");
+                    self.write_note(note, None)?;
+                } else if unmatched {
+                    // deleting would take the note's attachments, backlinks and owner
+                    // along if one of those files is this note
+                    self.conflict(
+                        id,
+                        &entry.file,
+                        "file missing while files without a known id are in the directory",
+                    );
+                    *held = true;
+                } else {
+                    self.service.delete_note(id)?;
+                    self.state.notes.remove(&id);
+                    self.report.removed_notes.push(id);
+                }
+            }
+            (Some(entry), Some((name, content))) => {
+                let db_changed = hash(&render_markdown(note)) != entry.db;
+                let disk_changed = hash(&content) != entry.disk;
+                if name != entry.file {
+                    // renamed on disk, the new name sticks
+                    if let Some(entry) = self.state.notes.get_mut(&id) {
+                        entry.file = name.clone();
+                    }
+                    self.report.renamed.push(id);
+                }
+                match (db_changed, disk_changed) {
+                    (false, false) => (),
+                    (true, false) => {
+                        self.write_note(note, Some(&name))?;
+                        self.report.written.push(id);
+                    }
+                    (false, true) => {
+                        self.apply_file(note.clone(), &name, &content)?;
+                        self.report.applied.push(id);
+                    }
+                    (true, true) => self.resolve(note, &name, &content)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// imports a file without a known id as a new note and gives it the id
+    fn create_from_file(&mut self, name: &str, content: &str) -> NoteResult<()> {
+        let parsed = parse_markdown(content);
+        let title = parsed
+            .title
+            .unwrap_or_else(|| name.trim_end_matches(".md").replace(['-', '_'], " "));
+        let mut note = Note::new(None, title, parsed.text);
+        note.due_at = parsed.due_at;
+        note.remind_at = parsed.remind_at;
+        note.completed = parsed.completed;
+        let id = self.service.create_notes(vec![note])?[0];
+        let created = self.service.fetch_note_by_id(id)?;
+        // keep the name the user chose, only the front-matter gains the id
+        let content = render_markdown(&created);
+        fs::write(self.dir.join(name), &content)?;
+        let hash = hash(&content);
+        self.state.notes.insert(
+            id,
+            Entry {
+                file: name.to_string(),
+                db: hash,
+                disk: hash,
+            },
+        );
+        self.report.created.push(id);
+        Ok(())
+    }
+
+    /// whether an id in a file's front-matter belongs to this database
+    fn known(&self, id: usize, notes: &BTreeMap<usize, Note>) -> bool {
+        notes.contains_key(&id) || self.state.notes.contains_key(&id)
+    }
+
+    /// both sides changed since the last sync
+    fn resolve(&mut self, note: &Note, name: &str, content: &str) -> NoteResult<()> {
+        let id = note.id.unwrap_or_default();
+        let conflict_copy = self
+            .dir
+            .join(format!("{}{CONFLICT_SUFFIX}", name.trim_end_matches(".md")));
+        if self.prefer != Prefer::Neither && conflict_copy.exists() {
+            fs::remove_file(&conflict_copy)?;
+        }
+        match self.prefer {
+            Prefer::Database => {
+                self.write_note(note, Some(name))?;
+                self.report.written.push(id);
+            }
+            Prefer::Disk => {
+                self.apply_file(note.clone(), name, content)?;
+                self.report.applied.push(id);
+            }
+            Prefer::Neither => {
+                fs::write(conflict_copy, render_markdown(note))?;
+                self.conflict(id, name, "changed in the database and on disk");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// mirrors every note to a Markdown file in `dir` and applies file edits back, in one pass
+pub fn sync_dir(service: &mut NoteService, dir: &Path, prefer: Prefer) -> NoteResult<SyncReport> {
+    fs::create_dir_all(dir)?;
+    let mut sync = Sync {
+        service,
+        dir,
+        prefer,
+        state: load_state(dir)?,
+        report: SyncReport::default(),
+    };
+    // whatever was written before an error is recorded, or the next pass would see
+    // those files as edited on both sides
+    let result = sync.run();
+    save_state(dir, &sync.state)?;
+    result.map(|()| sync.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_with(titles: &[&str]) -> NoteService {
+        let mut service = NoteService::in_memory();
+        service.initialize_notes_service().unwrap();
+        service
+            .create_notes(
+                titles
+                    .iter()
+                    .map(|title| Note::new(None, title.to_string(), format!("#Work on {title}")))
+                    .collect(),
+            )
+            .unwrap();
+        service
+    }
+
+    #[test]
+    fn front_matter_carries_timestamps_and_tags_but_reading_ignores_them() {
+        let service = service_with(&["first"]);
+        let note = service.fetch_note_by_id(1).unwrap();
+        let content = render_markdown(&note);
+        let created = note.created_at.unwrap().to_rfc3339();
+        assert!(content.contains(&format!("\ncreated: {created}\n")));
+        assert!(content.contains("\nupdated: "));
+        assert!(content.contains("\ntags: work\n"));
+
+        let edited = content
+            .replace(&format!("created: {created}"), "created: yesterday")
+            .replace("tags: work", "tags: play");
+        let parsed = parse_markdown(&edited);
+        assert_eq!(parsed.id, Some(1));
+        assert_eq!(parsed.title.as_deref(), Some("first"));
+        assert_eq!(parsed.text, "#Work on first");
+    }
+
+    #[test]
+    fn a_failing_note_is_reported_and_the_others_are_still_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut service = service_with(&["first", "second"]);
+        // writing the first note's file fails while this directory takes its name
+        fs::create_dir(dir.path().join("first-1.md")).unwrap();
+
+        let report = sync_dir(&mut service, dir.path(), Prefer::Neither).unwrap();
+        assert_eq!(report.written, [2]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].note_id, Some(1));
+        assert!(report.conflicts[0].reason.starts_with("could not sync"));
+
+        fs::remove_dir(dir.path().join("first-1.md")).unwrap();
+        let report = sync_dir(&mut service, dir.path(), Prefer::Neither).unwrap();
+        assert_eq!(report.written, [1]);
+        assert!(report.conflicts.is_empty() && report.applied.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+/// characters allowed in a tag after the `#`
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '/')
+}
+
+/// the tag a `#word` spells, or `None` for headings, `#42` style numbers and the like
+fn tag_of(word: &str) -> Option<String> {
+    let tag = word.strip_prefix('#')?;
+    let tag = tag.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    if tag.is_empty() || !tag.chars().all(is_tag_char) || !tag.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    Some(tag.to_lowercase())
+}
+
+/// returns every `#tag` in `text`, lowercased, in order of first appearance
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split_whitespace().filter_map(tag_of) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// checks a tag typed by the user, with or without its `#`
+pub fn normalize(tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    let word = if tag.starts_with('#') {
+        tag.to_string()
+    } else {
+        format!("#{}", tag)
+    };
+    tag_of(&word).ok_or_else(|| format!("`{}` is not a tag, use letters, digits, - _ or /", tag))
+}
+
+/// whether every word on the line is a tag
+fn is_tag_line(line: &str) -> bool {
+    !line.trim().is_empty() && line.split_whitespace().all(|word| tag_of(word).is_some())
+}
+
+/// appends `#tag` to the text, on the trailing tag line when there is one
+pub fn add_tag(text: &str, tag: &str) -> String {
+    if parse_tags(text).iter().any(|known| known == tag) {
+        return text.to_string();
+    }
+    let body = text.trim_end();
+    match body.rsplit_once('\n') {
+        _ if body.is_empty() => format!("#{}\n", tag),
+        Some((_, last)) if is_tag_line(last) => format!("{} #{}\n", body, tag),
+        None if is_tag_line(body) => format!("{} #{}\n", body, tag),
+        _ => format!("{}\n\n#{}\n", body, tag),
+    }
+}
+
+/// each whitespace separated word of the line with its byte offset
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(from)) => {
+                words.push((from, &line[from..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => (),
+        }
+    }
+    words
+}
+
+/// cuts the tag's words out of one line, each with the spaces after it, or before it
+/// when nothing follows, so the rest of the line keeps its spacing
+fn remove_from_line(line: &str, tag: &str) -> String {
+    let mut out = line.to_string();
+    // from the end, so the offsets of earlier words still hold
+    for (start, word) in words(line).into_iter().rev() {
+        if tag_of(word).as_deref() != Some(tag) {
+            continue;
+        }
+        let end = start + word.len();
+        let after = out[end..].len() - out[end..].trim_start().len();
+        let range = if end + after < out.len() {
+            start..end + after
+        } else {
+            out[..start].trim_end().len()..out.len()
+        };
+        out.replace_range(range, "");
+    }
+    out
+}
+
+/// drops every `#tag` from the text, and lines left empty by that
+pub fn remove_tag(text: &str, tag: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut dropped_line = false;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+        if !words(content)
+            .iter()
+            .any(|(_, word)| tag_of(word).as_deref() == Some(tag))
+        {
+            out.push_str(line);
+            continue;
+        }
+        let kept = remove_from_line(content, tag);
+        if kept.trim().is_empty() {
+            dropped_line = true;
+            continue;
+        }
+        out.push_str(&kept);
+        out.push_str(&line[content.len()..]);
+    }
+    if dropped_line && out.ends_with("\n\n") {
+        // the blank line `add_tag` put before the tag line
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_words_that_are_tags_only() {
+        let text = "#Work and #work, #42 # ## heading #a-b/c #x. mail@#y issue#3";
+        assert_eq!(parse_tags(text), ["work", "a-b/c", "x"]);
+    }
+
+    #[test]
+    fn normalizes_typed_tags() {
+        assert_eq!(normalize("#Work").unwrap(), "work");
+        assert_eq!(normalize(" work ").unwrap(), "work");
+        for bad in ["", "#", "#42", "two words", "a+b"] {
+            assert!(normalize(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn adds_to_the_trailing_tag_line_or_starts_one() {
+        assert_eq!(add_tag("", "t"), "#t\n");
+        assert_eq!(add_tag("text", "t"), "text\n\n#t\n");
+        assert_eq!(add_tag("text\n\n#a\n", "t"), "text\n\n#a #t\n");
+        assert_eq!(add_tag("#a", "t"), "#a #t\n");
+        assert_eq!(add_tag("has #T already", "t"), "has #T already");
+    }
+
+    #[test]
+    fn removes_only_the_tag_and_keeps_the_spacing_around_it() {
+        assert_eq!(
+            remove_tag("keep  two  spaces #t here", "t"),
+            "keep  two  spaces here"
+        );
+        assert_eq!(remove_tag("line ends #t", "t"), "line ends");
+        assert_eq!(remove_tag("#t starts\tit", "t"), "starts\tit");
+        assert_eq!(remove_tag("  - [ ] task #t\n", "t"), "  - [ ] task\n");
+        assert_eq!(remove_tag("a #T. b #tag2", "t"), "a b #tag2");
+        assert_eq!(remove_tag("a #t\r\nb", "t"), "a\r\nb");
+        assert_eq!(remove_tag("untouched  line", "t"), "untouched  line");
+    }
+
+    #[test]
+    fn removing_the_last_tag_drops_its_line() {
+        assert_eq!(remove_tag("text\n\n#a #t\n", "t"), "text\n\n#a\n");
+        assert_eq!(remove_tag(&add_tag("text", "t"), "t"), "text\n");
+        assert_eq!(remove_tag("#t #t\nrest", "t"), "rest");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::Note;
+
+/// a `- [ ]` / `- [x]` checklist item inside a note
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub note_id: usize,
+    pub note_title: String,
+    /// line of the item within the note text
+    pub line: usize,
+    pub done: bool,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskFilter {
+    All,
+    Open,
+    Done,
+}
+
+impl TaskFilter {
+    pub fn next(self) -> TaskFilter {
+        match self {
+            TaskFilter::All => TaskFilter::Open,
+            TaskFilter::Open => TaskFilter::Done,
+            TaskFilter::Done => TaskFilter::All,
+        }
+    }
+    pub fn accepts(self, task: &Task) -> bool {
+        match self {
+            TaskFilter::All => true,
+            TaskFilter::Open => !task.done,
+            TaskFilter::Done => task.done,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            TaskFilter::All => "all",
+            TaskFilter::Open => "open",
+            TaskFilter::Done => "done",
+        }
+    }
+}
+
+/// splits a checklist line into its done flag and item text
+fn parse_line(line: &str) -> Option<(bool, &str)> {
+    let rest = line.trim_start().strip_prefix("- [")?;
+    let mut chars = rest.chars();
+    let done = match chars.next()? {
+        ' ' => false,
+        'x' | 'X' => true,
+        _ => return None,
+    };
+    let rest = chars.as_str().strip_prefix(']')?;
+    Some((done, rest.trim()))
+}
+
+/// every checklist item in the note, in order
+pub fn parse_tasks(note: &Note) -> Vec<Task> {
+    note.text
+        .lines()
+        .enumerate()
+        .filter_map(|(line, content)| {
+            let (done, text) = parse_line(content)?;
+            Some(Task {
+                note_id: note.id.unwrap_or_default(),
+                note_title: note.title.clone(),
+                line,
+                done,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// flips the checkbox on `line`, leaving the text untouched if it is not an item
+pub fn toggle_task(text: &str, line: usize) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(i, content)| match parse_line(content) {
+            Some((done, _)) if i == line => {
+                let start = content.find("- [").unwrap_or_default() + 3;
+                let mark = if done { " " } else { "x" };
+                format!("{}{}{}", &content[..start], mark, &content[start + 1..])
+            }
+            _ => content.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str) -> Note {
+        Note::new(Some(4), "list".into(), text.into())
+    }
+
+    fn items(text: &str) -> Vec<(usize, bool, String)> {
+        parse_tasks(&note(text))
+            .into_iter()
+            .map(|task| (task.line, task.done, task.text))
+            .collect()
+    }
+
+    #[test]
+    fn parses_open_and_done_items() {
+        let text = "# groceries\n- [ ] milk\n- [x] eggs\n- [X]  bread \nplain line";
+        assert_eq!(
+            items(text),
+            [
+                (1, false, "milk".to_string()),
+                (2, true, "eggs".to_string()),
+                (3, true, "bread".to_string()),
+            ]
+        );
+        let task = &parse_tasks(&note(text))[0];
+        assert_eq!((task.note_id, task.note_title.as_str()), (4, "list"));
+    }
+
+    #[test]
+    fn parses_indented_items_but_not_other_bullets() {
+        let text = "  - [ ] nested\n\t- [x] tabbed\n* [ ] star\n-[ ] tight\n- [-] odd\n- [ x] wide";
+        assert_eq!(
+            items(text),
+            [
+                (0, false, "nested".to_string()),
+                (1, true, "tabbed".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn toggles_only_the_item_on_the_given_line() {
+        let text = "intro\n  - [ ] first\n- [x] second\n";
+        assert_eq!(toggle_task(text, 1), "intro\n  - [x] first\n- [x] second\n");
+        assert_eq!(toggle_task(text, 2), "intro\n  - [ ] first\n- [ ] second\n");
+        assert_eq!(toggle_task(text, 0), text);
+        assert_eq!(toggle_task(text, 9), text);
+        assert_eq!(toggle_task(&toggle_task(text, 1), 1), text);
+    }
+
+    #[test]
+    fn filters_by_state() {
+        let tasks = parse_tasks(&note("- [ ] open\n- [x] done"));
+        let count = |filter: TaskFilter| tasks.iter().filter(|task| filter.accepts(task)).count();
+        assert_eq!(
+            (
+                count(TaskFilter::All),
+                count(TaskFilter::Open),
+                count(TaskFilter::Done)
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(TaskFilter::Done.next(), TaskFilter::All);
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::error::{NoteError, NoteResult};
+
+const EXTENSION: &str = "md";
+const CURSOR: &str = "{{cursor}}";
+
+/// note text produced from a template
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expanded {
+    pub text: String,
+    /// 1-based line and column where `{{cursor}}` stood
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// fills in `{{date}}`, `{{time}}` and `{{title}}`, and drops `{{cursor}}` recording where it was
+///
+/// unknown placeholders are left as they are
+pub fn expand_template(body: &str, title: &str, now: DateTime<Local>) -> Expanded {
+    let text = body
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{title}}", title);
+    let cursor = text.find(CURSOR).map(|at| {
+        let before = &text[..at];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+        (line, column)
+    });
+    Expanded {
+        text: text.replace(CURSOR, ""),
+        cursor,
+    }
+}
+
+/// templates kept as `<name>.md` files in one directory
+pub struct TemplateDir {
+    dir: PathBuf,
+}
+
+impl TemplateDir {
+    pub fn new(dir: impl Into<PathBuf>) -> TemplateDir {
+        TemplateDir { dir: dir.into() }
+    }
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+    fn file(&self, name: &str) -> NoteResult<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && !name.contains(['/', '\\'])
+            && !name.chars().any(char::is_whitespace);
+        if !valid {
+            return Err(NoteError::Invalid(format!(
+                "`{}` is not a template name, use one word without slashes",
+                name
+            )));
+        }
+        Ok(self.dir.join(format!("{}.{}", name, EXTENSION)))
+    }
+
+    /// template names, sorted, none when the directory does not exist yet
+    pub fn list(&self) -> NoteResult<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+    pub fn get(&self, name: &str) -> NoteResult<String> {
+        match fs::read_to_string(self.file(name)?) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(NoteError::TemplateNotFound(name.to_string()))
+            }
+            result => Ok(result?),
+        }
+    }
+    /// creates or replaces the template
+    pub fn save(&self, name: &str, body: &str) -> NoteResult<()> {
+        let file = self.file(name)?;
+        fs::create_dir_all(&self.dir)?;
+        Ok(fs::write(file, body)?)
+    }
+    pub fn remove(&self, name: &str) -> NoteResult<()> {
+        match fs::remove_file(self.file(name)?) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(NoteError::TemplateNotFound(name.to_string()))
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 9, 7, 5, 0).unwrap()
+    }
+
+    #[test]
+    fn fills_in_date_time_and_title() {
+        let expanded = expand_template("# {{title}}\n{{date}} {{time}} {{date}}", "Standup", now());
+        assert_eq!(expanded.text, "# Standup\n2024-03-09 07:05 2024-03-09");
+        assert_eq!(expanded.cursor, None);
+    }
+
+    #[test]
+    fn drops_the_cursor_and_records_where_it_was() {
+        let expanded = expand_template("# {{title}}\n\n- {{cursor}}\n", "Standup", now());
+        assert_eq!(expanded.text, "# Standup\n\n- \n");
+        assert_eq!(expanded.cursor, Some((3, 3)));
+
+        let expanded = expand_template("{{cursor}}{{title}}", "t", now());
+        assert_eq!(
+            (expanded.text.as_str(), expanded.cursor),
+            ("t", Some((1, 1)))
+        );
+    }
+
+    #[test]
+    fn the_cursor_column_counts_after_the_placeholders_are_filled() {
+        let expanded = expand_template("{{date}} {{cursor}}", "t", now());
+        assert_eq!(expanded.cursor, Some((1, 12)));
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders_alone() {
+        let body = "{{author}} {{ date }} {{Date}} {date} {{title";
+        assert_eq!(expand_template(body, "t", now()).text, body);
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_one_plain_word() {
+        let dir = TemplateDir::new("templates");
+        assert!(dir.file("daily").is_ok());
+        for name in ["", ".hidden", "a/b", "a\\b", "two words"] {
+            assert!(dir.file(name).is_err(), "{name:?}");
+        }
+    }
+}
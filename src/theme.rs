@@ -0,0 +1,324 @@
+use std::path::Path;
+use std::{env, fs};
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+/// themes that need no file, the first is used when none is configured
+pub const BUILT_IN: [&str; 3] = ["dark", "light", "high-contrast"];
+/// how many theme files may be stacked on each other through `base`
+const MAX_BASE_DEPTH: usize = 8;
+
+/// the styles every widget draws with, by what they mark rather than by color
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// plain text and borders
+    pub text: Style,
+    /// prompts, messages and the active tab
+    pub accent: Style,
+    /// links, backlinks, attachments and note titles next to tasks
+    pub link: Style,
+    pub heading: Style,
+    /// secondary text such as switcher snippets
+    pub muted: Style,
+    /// the highlighted row or item
+    pub selected: Style,
+    /// characters matched by the quick switcher
+    pub matched: Style,
+    /// status bar toasts
+    pub success: Style,
+    pub warning: Style,
+    pub error: Style,
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            text: Style::default().fg(Color::White),
+            accent: Style::default().fg(Color::Yellow),
+            link: Style::default().fg(Color::LightCyan),
+            heading: Style::default().fg(Color::LightBlue),
+            muted: Style::default().fg(Color::DarkGray),
+            selected: Style::default()
+                .bg(Color::Yellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            matched: Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            success: Style::default().fg(Color::LightGreen),
+            warning: Style::default().fg(Color::LightYellow),
+            error: Style::default().fg(Color::LightRed),
+        }
+    }
+    /// for terminals with a light background
+    pub fn light() -> Theme {
+        Theme {
+            name: "light".to_string(),
+            text: Style::default().fg(Color::Black),
+            accent: Style::default().fg(Color::Magenta),
+            link: Style::default().fg(Color::Blue),
+            heading: Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            muted: Style::default().fg(Color::Gray),
+            selected: Style::default()
+                .bg(Color::Blue)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            matched: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            success: Style::default().fg(Color::Green),
+            // dark orange, yellow is unreadable on white
+            warning: Style::default().fg(Color::Indexed(130)),
+            error: Style::default().fg(Color::Red),
+        }
+    }
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high-contrast".to_string(),
+            text: Style::default().fg(Color::White).bg(Color::Black),
+            accent: Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            link: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::UNDERLINED),
+            heading: Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            muted: Style::default().fg(Color::Gray),
+            selected: Style::default()
+                .bg(Color::White)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            matched: Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            success: Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            warning: Style::default()
+                .bg(Color::LightYellow)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            error: Style::default()
+                .bg(Color::Red)
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        }
+    }
+    pub fn built_in(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// a built-in theme or `<dir>/<name>.json`, without colors when `NO_COLOR` is set
+    pub fn load(name: &str, dir: &Path) -> Result<Theme, String> {
+        let theme = match Theme::built_in(name) {
+            Some(theme) => theme,
+            None => Theme::read(name, dir, 0)?,
+        };
+        Ok(if no_color() {
+            theme.without_color()
+        } else {
+            theme
+        })
+    }
+
+    fn read(name: &str, dir: &Path, depth: usize) -> Result<Theme, String> {
+        if let Some(theme) = Theme::built_in(name) {
+            return Ok(theme);
+        }
+        if depth > MAX_BASE_DEPTH {
+            return Err(format!(
+                "theme `{}` is based on too many others, or on itself",
+                name
+            ));
+        }
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(format!("`{}` is not a theme name", name));
+        }
+        let path = dir.join(format!("{}.json", name));
+        if !path.exists() {
+            return Err(format!(
+                "no theme named `{}`, expected {} or a file in {}",
+                name,
+                BUILT_IN.join(", "),
+                dir.display()
+            ));
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        let file: ThemeFile = serde_json::from_str(&content)
+            .map_err(|err| format!("could not parse {}: {}", path.display(), err))?;
+        let mut theme = Theme::read(file.base.as_deref().unwrap_or(BUILT_IN[0]), dir, depth + 1)?;
+        theme.name = name.to_string();
+        let roles = [
+            (&mut theme.text, &file.text, "text"),
+            (&mut theme.accent, &file.accent, "accent"),
+            (&mut theme.link, &file.link, "link"),
+            (&mut theme.heading, &file.heading, "heading"),
+            (&mut theme.muted, &file.muted, "muted"),
+            (&mut theme.selected, &file.selected, "selected"),
+            (&mut theme.matched, &file.matched, "matched"),
+            (&mut theme.success, &file.success, "success"),
+            (&mut theme.warning, &file.warning, "warning"),
+            (&mut theme.error, &file.error, "error"),
+        ];
+        for (style, spec, role) in roles {
+            if let Some(spec) = spec {
+                *style = parse_style(spec)
+                    .map_err(|err| format!("{} in {}: {}", role, path.display(), err))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    /// keeps bold, underline and the like, and marks with them what colors marked
+    pub fn without_color(self) -> Theme {
+        let strip = |style: Style| {
+            Style::default()
+                .add_modifier(style.add_modifier)
+                .remove_modifier(style.sub_modifier)
+        };
+        Theme {
+            name: self.name,
+            text: strip(self.text),
+            accent: strip(self.accent),
+            link: strip(self.link),
+            heading: strip(self.heading).add_modifier(Modifier::BOLD),
+            muted: strip(self.muted),
+            selected: strip(self.selected).add_modifier(Modifier::REVERSED),
+            matched: strip(self.matched).add_modifier(Modifier::UNDERLINED),
+            success: strip(self.success),
+            warning: strip(self.warning).add_modifier(Modifier::BOLD),
+            error: strip(self.error).add_modifier(Modifier::BOLD),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::dark()
+    }
+}
+
+/// a theme file, each role overriding the one of its base theme
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    text: Option<String>,
+    accent: Option<String>,
+    link: Option<String>,
+    heading: Option<String>,
+    muted: Option<String>,
+    selected: Option<String>,
+    matched: Option<String>,
+    success: Option<String>,
+    warning: Option<String>,
+    error: Option<String>,
+}
+
+/// <https://no-color.org>: set and not empty
+fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// built-in themes followed by the ones in `dir`, for completing `:theme`
+pub fn available(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .filter(|name| !BUILT_IN.contains(&name.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    BUILT_IN
+        .iter()
+        .map(|name| name.to_string())
+        .chain(files)
+        .collect()
+}
+
+/// `yellow`, `bold white on blue`, `#ff8800`, `underlined 208`: modifiers, a foreground
+/// and `on` a background, each optional
+pub fn parse_style(spec: &str) -> Result<Style, String> {
+    let mut style = Style::default();
+    let mut fg = None;
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        let modifier = match word.to_lowercase().as_str() {
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" | "underline" => Modifier::UNDERLINED,
+            "reversed" | "reverse" => Modifier::REVERSED,
+            "on" => {
+                let color = words
+                    .next()
+                    .ok_or_else(|| format!("`{}` ends with `on` and no background", spec))?;
+                style = style.bg(parse_color(color)?);
+                continue;
+            }
+            _ if fg.is_some() => return Err(format!("`{}` names two foreground colors", spec)),
+            _ => {
+                fg = Some(parse_color(word)?);
+                continue;
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    if let Some(fg) = fg {
+        style = style.fg(fg);
+    }
+    Ok(style)
+}
+
+fn parse_color(word: &str) -> Result<Color, String> {
+    if let Some(hex) = word.strip_prefix('#') {
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+            _ => Err(format!("`{}` is not a #rrggbb color", word)),
+        };
+    }
+    if let Ok(index) = word.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+    let color = match word.to_lowercase().replace(['-', '_'], "").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return Err(format!("unknown color `{}`", word)),
+    };
+    Ok(color)
+}
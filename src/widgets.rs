@@ -1,24 +1,35 @@
+use std::path::Path;
+
+use chrono::{Datelike, Weekday};
 use tui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Span, Spans},
     widgets::{
-        Block, BorderType, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, Tabs,
-        Wrap,
+        BarChart, Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap,
     },
 };
 
-use crate::{app::AppState, Note};
+use crate::{
+    app::{AppState, CommandLine, QuickSwitcher, Toast, ToastKind},
+    command::Action,
+    journal,
+    keymap::{self, Key, Mode},
+    schedule,
+    theme::Theme,
+    Attachment, Dashboard, Note, Task, TaskFilter,
+};
 
 pub struct Widget {
     pub header: Rect,
     pub main_part: Rect,
     pub footer: Rect,
     pub all: Rect,
+    pub theme: Theme,
 }
 
 impl Widget {
-    pub fn new(size: Rect) -> Widget {
+    pub fn new(size: Rect, theme: Theme) -> Widget {
         let chunks = Layout::default()
             .direction(tui::layout::Direction::Vertical)
             .margin(2)
@@ -36,36 +47,82 @@ impl Widget {
             main_part: chunks[1],
             footer: chunks[2],
             all: size,
+            theme,
         }
     }
-    pub fn render_copyright<'a>(&self) -> Paragraph<'a> {
-        let copyright = Paragraph::new("BerserkerMother")
-            .style(Style::default().fg(Color::LightCyan))
-            .alignment(Alignment::Center)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Copyright")
-                    .title_alignment(Alignment::Center)
-                    .style(Style::default().fg(Color::LightCyan))
-                    .border_type(BorderType::Plain),
-            );
-        copyright
+    /// mode, note count and database, with any half typed key sequence
+    pub fn render_status_bar<'a>(
+        &self,
+        mode: &'static str,
+        note_count: usize,
+        db_path: String,
+        pending: Option<String>,
+    ) -> Paragraph<'a> {
+        let mut spans = vec![
+            Span::styled(format!(" {} ", mode), self.theme.selected),
+            Span::raw(match note_count {
+                1 => "  1 note  ".to_string(),
+                count => format!("  {} notes  ", count),
+            }),
+            Span::styled(db_path, self.theme.muted),
+        ];
+        if let Some(pending) = pending {
+            spans.push(Span::styled(format!("  {}", pending), self.theme.accent));
+        }
+        Paragraph::new(Spans::from(spans)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.text)
+                .border_type(BorderType::Plain),
+        )
+    }
+    /// the right end of the status bar, wide enough for the text
+    pub fn toast_area(&self, text: &str) -> Rect {
+        let inner = Layout::default()
+            .margin(1)
+            .constraints([Constraint::Min(1)].as_ref())
+            .split(self.footer)[0];
+        let width = (text.chars().count() as u16 + 2).min(inner.width);
+        Rect {
+            x: inner.x + inner.width - width,
+            width,
+            ..inner
+        }
+    }
+    pub fn render_toast<'a>(&self, toast: &Toast) -> Paragraph<'a> {
+        let style = match toast.kind {
+            ToastKind::Success => self.theme.success,
+            ToastKind::Warning => self.theme.warning,
+            ToastKind::Error => self.theme.error,
+        };
+        Paragraph::new(format!(" {} ", toast.text))
+            .style(style)
+            .alignment(Alignment::Right)
+    }
+    pub fn render_command_line<'a>(&self, line: &CommandLine) -> Paragraph<'a> {
+        let name = line.input.split_whitespace().next().unwrap_or_default();
+        let title = match Action::from_name(name) {
+            _ if line.completions.len() > 1 => line.completions.join("  "),
+            Some(action) => format!("{}: {}", action.name(), action.description()),
+            None => "Command (Tab completes, Up/Down history, Esc closes)".to_string(),
+        };
+        Paragraph::new(format!(":{}", line.input)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.text)
+                .title(title)
+                .border_type(BorderType::Double),
+        )
     }
     pub fn render_tabs<'a>(&self, active_menu_item: AppState) -> Tabs<'a> {
-        let menu_titles = vec!["Home", "Notes", "Add", "Delete", "Quit"];
+        let menu_titles = ["Home", "Notes", "Add", "Delete", "Tasks", "Journal", "Quit"];
         let menu = menu_titles
             .iter()
             .map(|t| {
                 let (first, rest) = t.split_at(1);
                 Spans::from(vec![
-                    Span::styled(
-                        first,
-                        Style::default()
-                            .fg(Color::Yellow)
-                            .add_modifier(Modifier::UNDERLINED),
-                    ),
-                    Span::styled(rest, Style::default().fg(Color::White)),
+                    Span::styled(first, self.theme.accent.add_modifier(Modifier::UNDERLINED)),
+                    Span::styled(rest, self.theme.text),
                 ])
             })
             .collect();
@@ -73,23 +130,30 @@ impl Widget {
         let tabs = Tabs::new(menu)
             .select(active_menu_item.into())
             .block(Block::default().title("Menu").borders(Borders::ALL))
-            .style(Style::default().fg(Color::White))
-            .highlight_style(Style::default().fg(Color::Yellow))
+            .style(self.theme.text)
+            .highlight_style(self.theme.accent)
             .divider(Span::raw("|"));
         tabs
     }
-    pub fn render_add_note<'a>(&self, buffer: &'a str) -> Paragraph<'a> {
+    pub fn render_add_note<'a>(&self, buffer: &'a str, template: Option<&str>) -> Paragraph<'a> {
+        let title = match template {
+            Some(template) => format!("title, from template `{}` (F1 lists the keys)", template),
+            None => "title (F1 lists the keys)".to_string(),
+        };
+        self.render_prompt(buffer, title)
+    }
+    pub fn render_prompt<'a>(&self, buffer: &'a str, title: impl Into<String>) -> Paragraph<'a> {
         let text = Paragraph::new(vec![Spans::from(vec![Span::styled(
             buffer,
-            Style::default().fg(Color::White),
+            self.theme.text,
         )])])
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .style(Style::default().fg(Color::Yellow))
-                .title("title")
+                .style(self.theme.accent)
+                .title(title.into())
                 .border_type(tui::widgets::BorderType::Double) // Use double lines for the border.
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(self.theme.accent),
         )
         .alignment(Alignment::Center) // Center the text horizontally.
         .wrap(Wrap { trim: true });
@@ -120,36 +184,315 @@ impl Widget {
                 .as_ref(),
             )
             .split(chunks[1]);
-        return middle_chunk[1];
+        middle_chunk[1]
     }
 
+    /// query line and match list of the quick switcher, centered over the tab
+    pub fn switcher_area(&self) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(3)].as_ref())
+            .split(self.popup_area())
+    }
+    /// 60% by 70% of the screen, centered
+    pub fn popup_area(&self) -> Rect {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(70),
+                    Constraint::Percentage(15),
+                ]
+                .as_ref(),
+            )
+            .split(self.all);
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(20),
+                ]
+                .as_ref(),
+            )
+            .split(rows[1])[1]
+    }
+    /// one line per action: its keys, the name it runs by on the `:` line and what it does
+    pub fn render_help<'a>(
+        &self,
+        entries: &[(Vec<Vec<Key>>, Action)],
+        mode: Mode,
+    ) -> Paragraph<'a> {
+        let keys: Vec<String> = entries
+            .iter()
+            .map(|(sequences, _)| {
+                sequences
+                    .iter()
+                    .map(|keys| keymap::sequence_label(keys))
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            })
+            .collect();
+        let width = keys
+            .iter()
+            .map(|keys| keys.chars().count())
+            .max()
+            .unwrap_or(0);
+        let name_width = entries
+            .iter()
+            .map(|(_, action)| action.name().len())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<_> = entries
+            .iter()
+            .zip(keys)
+            .map(|((_, action), keys)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{:<width$}  ", keys, width = width),
+                        self.theme.accent.add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        format!(":{:<width$}  ", action.name(), width = name_width),
+                        self.theme.link,
+                    ),
+                    Span::raw(action.description()),
+                ])
+            })
+            .collect();
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(format!("Keys in {} mode (any key closes)", mode.name()))
+                    .border_type(BorderType::Double),
+            )
+            .wrap(Wrap { trim: false })
+    }
+    /// a blank note first, then every template
+    pub fn render_template_picker<'a>(&self, names: &[String], dir: &Path) -> List<'a> {
+        let mut items = vec![ListItem::new(Span::styled("blank note", self.theme.muted))];
+        items.extend(names.iter().map(|name| ListItem::new(name.clone())));
+        let title = if names.is_empty() {
+            format!(
+                "No templates in {} yet, add one with :template edit <name>",
+                dir.display()
+            )
+        } else {
+            "Start from template (Enter picks, Esc keeps the current one)".to_string()
+        };
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(title)
+                    .border_type(BorderType::Double),
+            )
+            .highlight_style(self.theme.selected)
+    }
+    pub fn render_switcher<'a>(&self, switcher: &QuickSwitcher) -> (Paragraph<'a>, List<'a>) {
+        let prompt = Paragraph::new(format!("> {}", switcher.query)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.accent)
+                .title("Go to note (Enter opens, Esc closes)")
+                .border_type(BorderType::Double),
+        );
+        let items: Vec<_> = switcher
+            .matches
+            .iter()
+            .map(|found| {
+                let mut spans: Vec<Span> = found
+                    .note
+                    .title
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let style = if found.positions.contains(&i) {
+                            self.theme.matched
+                        } else {
+                            Style::default()
+                        };
+                        Span::styled(c.to_string(), style)
+                    })
+                    .collect();
+                if let Some(snippet) = &found.snippet {
+                    spans.push(Span::styled(format!("  {}", snippet), self.theme.muted));
+                }
+                ListItem::new(Spans::from(spans))
+            })
+            .collect();
+        let title = format!(
+            "{} of {} notes",
+            switcher.matches.len(),
+            switcher.notes.len()
+        );
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(title)
+                    .border_type(BorderType::Double),
+            )
+            .highlight_style(self.theme.selected);
+        (prompt, list)
+    }
+    /// summary, notes created this week, recently edited notes and upcoming reminders
+    pub fn home_area(&self) -> Vec<Rect> {
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(self.main_part);
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(9), Constraint::Min(5)].as_ref())
+            .split(halves[0]);
+        let right = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(halves[1]);
+        vec![left[0], left[1], right[0], right[1]]
+    }
     /// app high level widgets
-    pub fn render_home<'a>(&self) -> Paragraph<'a> {
-        let home = Paragraph::new(vec![
-    Spans::from(vec![Span::raw("")]),
-    Spans::from(vec![Span::raw("Welcome")]),
-    Spans::from(vec![Span::raw("")]),
-    Spans::from(vec![Span::raw("to")]),
-    Spans::from(vec![Span::raw("")]),
-    Spans::from(vec![Span::styled(
-        "pet-CLI",
-        Style::default().fg(Color::LightBlue),
-    )]),
-    Spans::from(vec![Span::raw("")]),
-    Spans::from(vec![Span::raw("Press 'n' to access notes, 'a' to add new note and 'd' to delete the currently selected note.")]),
-])
-.alignment(Alignment::Center)
-.block(
-    Block::default()
-        .borders(Borders::ALL)
-        .style(Style::default().fg(Color::White))
-        .title("Home")
-        .border_type(BorderType::Plain),
-);
-        home
-    }
-    pub fn home_area(&self) -> Rect {
-        self.main_part
+    pub fn render_home<'a>(&self, dashboard: &Dashboard) -> Paragraph<'a> {
+        let tags = if dashboard.top_tags.is_empty() {
+            "none yet".to_string()
+        } else {
+            dashboard
+                .top_tags
+                .iter()
+                .map(|(tag, count)| format!("#{} ({})", tag, count))
+                .collect::<Vec<_>>()
+                .join("  ")
+        };
+        Paragraph::new(vec![
+            Spans::from(vec![Span::styled("pet-CLI", self.theme.heading)]),
+            Spans::from(vec![Span::raw("")]),
+            Spans::from(vec![Span::raw(format!(
+                "{} notes, {} open tasks",
+                dashboard.total_notes, dashboard.open_tasks
+            ))]),
+            Spans::from(vec![
+                Span::styled("Top tags: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(tags, self.theme.link),
+            ]),
+            Spans::from(vec![Span::raw("")]),
+            Spans::from(vec![Span::styled(
+                "Enter opens the selected recent note, '?' lists every key, ':' runs a command.",
+                self.theme.muted,
+            )]),
+        ])
+        .alignment(Alignment::Center)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.text)
+                .title("Home")
+                .border_type(BorderType::Plain),
+        )
+        .wrap(Wrap { trim: true })
+    }
+    /// one bar per day, labelled with the weekday
+    pub fn week_bars(dashboard: &Dashboard) -> Vec<(&'static str, u64)> {
+        dashboard
+            .created_per_day
+            .iter()
+            .map(|(day, count)| {
+                let label = match day.weekday() {
+                    Weekday::Mon => "Mon",
+                    Weekday::Tue => "Tue",
+                    Weekday::Wed => "Wed",
+                    Weekday::Thu => "Thu",
+                    Weekday::Fri => "Fri",
+                    Weekday::Sat => "Sat",
+                    Weekday::Sun => "Sun",
+                };
+                (label, *count)
+            })
+            .collect()
+    }
+    pub fn render_week_chart<'a>(&self, bars: &'a [(&'a str, u64)]) -> BarChart<'a> {
+        let inner_width = self.home_area()[1].width.saturating_sub(2) as usize;
+        let bar_width = (inner_width / bars.len().max(1)).saturating_sub(1).max(3);
+        BarChart::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(format!(
+                        "Notes created this week: {}",
+                        bars.iter().map(|(_, count)| count).sum::<u64>()
+                    ))
+                    .border_type(BorderType::Plain),
+            )
+            .data(bars)
+            .bar_width(bar_width as u16)
+            .bar_gap(1)
+            .bar_style(self.theme.accent)
+            .value_style(self.theme.selected)
+            .label_style(self.theme.text)
+    }
+    pub fn render_recent<'a>(&self, recent: &[Note]) -> List<'a> {
+        let items: Vec<_> = if recent.is_empty() {
+            vec![ListItem::new(Span::styled(
+                "nothing edited yet",
+                self.theme.muted,
+            ))]
+        } else {
+            recent
+                .iter()
+                .map(|note| {
+                    let edited = note.updated_at.map(schedule::format_when);
+                    ListItem::new(Spans::from(vec![
+                        Span::raw(note.title.clone()),
+                        Span::styled(
+                            format!("  {}", edited.unwrap_or_default()),
+                            self.theme.muted,
+                        ),
+                    ]))
+                })
+                .collect()
+        };
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title("Recently edited (Enter opens)")
+                    .border_type(BorderType::Plain),
+            )
+            .highlight_style(self.theme.selected)
+    }
+    pub fn render_upcoming<'a>(&self, upcoming: &[Note]) -> List<'a> {
+        let items: Vec<_> = if upcoming.is_empty() {
+            vec![ListItem::new(Span::styled(
+                "no reminders set",
+                self.theme.muted,
+            ))]
+        } else {
+            upcoming
+                .iter()
+                .map(|note| {
+                    let when = note.remind_at.map(schedule::format_when);
+                    ListItem::new(Spans::from(vec![
+                        Span::styled(format!("{}  ", when.unwrap_or_default()), self.theme.accent),
+                        Span::raw(note.title.clone()),
+                    ]))
+                })
+                .collect()
+        };
+        List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.text)
+                .title("Upcoming reminders")
+                .border_type(BorderType::Plain),
+        )
     }
     pub fn notes_area(&self) -> Vec<Rect> {
         Layout::default()
@@ -157,15 +500,83 @@ impl Widget {
             .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
             .split(self.main_part)
     }
+    /// detail pane split into the note text and its links/backlinks
+    pub fn note_detail_area(&self) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(5)].as_ref())
+            .split(self.notes_area()[1])
+    }
+    pub fn render_links<'a>(
+        &self,
+        links: &[String],
+        selected_link: Option<usize>,
+        backlinks: &[Note],
+        attachments: &[Attachment],
+        selected_attachment: Option<usize>,
+    ) -> Paragraph<'a> {
+        let mut outgoing = vec![Span::styled(
+            "Links: ",
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        for (i, link) in links.iter().enumerate() {
+            let style = if selected_link == Some(i) {
+                self.theme.selected
+            } else {
+                self.theme.link
+            };
+            outgoing.push(Span::styled(format!("[[{}]]", link), style));
+            outgoing.push(Span::raw(" "));
+        }
+        let mut incoming = vec![Span::styled(
+            "Backlinks: ",
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        for note in backlinks {
+            incoming.push(Span::styled(note.title.clone(), self.theme.link));
+            incoming.push(Span::raw(" "));
+        }
+        let mut files = vec![Span::styled(
+            "Attachments: ",
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        for (i, attachment) in attachments.iter().enumerate() {
+            let style = if selected_attachment == Some(i) {
+                self.theme.selected
+            } else {
+                self.theme.link
+            };
+            files.push(Span::styled(
+                format!("{} ({})", attachment.name, attachment.display_size()),
+                style,
+            ));
+            files.push(Span::raw(" "));
+        }
+        Paragraph::new(vec![
+            Spans::from(outgoing),
+            Spans::from(incoming),
+            Spans::from(files),
+        ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(self.theme.text)
+                .title("Links (Tab/Enter/Backspace) - Attachments (v select, o open, s save)")
+                .border_type(BorderType::Plain),
+        )
+        .wrap(Wrap { trim: true })
+    }
     pub fn render_notes<'a>(
         &self,
         note_list_state: &ListState,
-        note_list: &Vec<Note>,
-    ) -> (List<'a>, Table<'a>) {
+        note_list: &[Note],
+        selected_task_line: Option<usize>,
+        title: String,
+    ) -> (List<'a>, Paragraph<'a>) {
         let notes = Block::default()
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::White))
-            .title("Notes")
+            .style(self.theme.text)
+            .title(title)
             .border_type(BorderType::Plain);
 
         let items: Vec<_> = note_list
@@ -177,38 +588,178 @@ impl Widget {
                 )]))
             })
             .collect();
-        let selected_note = note_list
-            .get(
-                note_list_state
-                    .selected()
-                    .expect("there is always a selected note"),
-            )
-            .expect("exists")
-            .clone();
+        let selected_note = note_list_state
+            .selected()
+            .and_then(|i| note_list.get(i))
+            .cloned()
+            .unwrap_or_else(|| Note::new(None, String::new(), String::new()));
 
-        let list = List::new(items).block(notes).highlight_style(
-            Style::default()
-                .bg(Color::Yellow)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
-        );
+        let list = List::new(items)
+            .block(notes)
+            .highlight_style(self.theme.selected);
 
-        let note_detail = Table::new(vec![Row::new(vec![Cell::from(Span::raw(
-            selected_note.text,
-        ))])])
-        .header(Row::new(vec![Cell::from(Span::styled(
-            "Text",
-            Style::default().add_modifier(Modifier::BOLD),
-        ))]))
+        let text: Vec<_> = selected_note
+            .text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let style = if selected_task_line == Some(i) {
+                    self.theme.selected
+                } else {
+                    Style::default()
+                };
+                Spans::from(vec![Span::styled(line.to_string(), style)])
+            })
+            .collect();
+        let title = match selected_note.due_at {
+            Some(due_at) if selected_note.completed => {
+                format!("Detail - done, was due {}", schedule::format_when(due_at))
+            }
+            Some(due_at) => format!(
+                "Detail - due {} (r reschedule)",
+                schedule::format_when(due_at)
+            ),
+            None => "Detail (c next item, Space toggle, r set due)".to_string(),
+        };
+        let note_detail = Paragraph::new(text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(title)
+                    .border_type(BorderType::Plain),
+            )
+            .wrap(Wrap { trim: false });
+
+        (list, note_detail)
+    }
+    /// shown on the Notes tab instead of the list when there is nothing to list
+    pub fn render_empty_notes<'a>(&self, filtered: bool, title: String) -> Paragraph<'a> {
+        let hint = if filtered {
+            "No notes match the current filters. Press 'F' to change the scope filter or 'O' the owner filter."
+        } else {
+            "No notes yet. Press 'a' to add your first note, type a title and press Ctrl+E to write it in vim."
+        };
+        Paragraph::new(vec![
+            Spans::from(vec![Span::raw("")]),
+            Spans::from(vec![Span::styled("Nothing here", self.theme.heading)]),
+            Spans::from(vec![Span::raw("")]),
+            Spans::from(vec![Span::raw(hint)]),
+        ])
+        .alignment(Alignment::Center)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .style(Style::default().fg(Color::White))
-                .title("Detail")
+                .style(self.theme.text)
+                .title(title)
                 .border_type(BorderType::Plain),
         )
-        .widths(&[Constraint::Percentage(100)]);
-
-        (list, note_detail)
+        .wrap(Wrap { trim: true })
+    }
+    /// both sides of an edit conflict, split side by side
+    pub fn conflict_area(&self) -> Vec<Rect> {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(self.main_part)
+    }
+    pub fn render_conflict<'a>(
+        &self,
+        mine: &Note,
+        theirs: &Note,
+    ) -> (Paragraph<'a>, Paragraph<'a>) {
+        let side = |note: &Note, title: String, style: Style| {
+            Paragraph::new(note.text.clone())
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .style(style)
+                        .title(title)
+                        .border_type(BorderType::Double),
+                )
+                .wrap(Wrap { trim: false })
+        };
+        (
+            side(
+                mine,
+                format!("Yours: {} (m keep mine, e merge in editor)", mine.title),
+                self.theme.accent,
+            ),
+            side(
+                theirs,
+                format!(
+                    "Saved elsewhere, version {}: {} (t take theirs)",
+                    theirs.version, theirs.title
+                ),
+                self.theme.link,
+            ),
+        )
+    }
+    /// daily notes with their weekday and first line
+    pub fn render_journal<'a>(&self, journal: &[Note]) -> List<'a> {
+        let items: Vec<_> = if journal.is_empty() {
+            vec![ListItem::new(Span::styled(
+                "no daily notes yet, press t to start today's",
+                self.theme.muted,
+            ))]
+        } else {
+            journal
+                .iter()
+                .map(|note| {
+                    let weekday = journal::date_of(note)
+                        .map(|date| date.format("%a").to_string())
+                        .unwrap_or_default();
+                    // the first line that is more than the date again
+                    let first_line = note
+                        .text
+                        .lines()
+                        .map(str::trim)
+                        .find(|line| {
+                            !line.is_empty() && line.trim_start_matches('#').trim() != note.title
+                        })
+                        .unwrap_or_default();
+                    ListItem::new(Spans::from(vec![
+                        Span::raw(format!("{} {}  ", note.title, weekday)),
+                        Span::styled(first_line.to_string(), self.theme.muted),
+                    ]))
+                })
+                .collect()
+        };
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(format!(
+                        "Journal: {} days (Enter opens, t today, [ and ] step through days)",
+                        journal.len()
+                    ))
+                    .border_type(BorderType::Plain),
+            )
+            .highlight_style(self.theme.selected)
+    }
+    pub fn render_tasks<'a>(&self, tasks: &[Task], filter: TaskFilter) -> List<'a> {
+        let items: Vec<_> = tasks
+            .iter()
+            .map(|task| {
+                let mark = if task.done { "[x]" } else { "[ ]" };
+                ListItem::new(Spans::from(vec![
+                    Span::raw(format!("{} {} ", mark, task.text)),
+                    Span::styled(format!("({})", task.note_title), self.theme.link),
+                ]))
+            })
+            .collect();
+        List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .style(self.theme.text)
+                    .title(format!(
+                        "Tasks: {} (f filter, Space toggle, Enter open note)",
+                        filter.name()
+                    ))
+                    .border_type(BorderType::Plain),
+            )
+            .highlight_style(self.theme.selected)
     }
 }